@@ -0,0 +1,212 @@
+//! Asynchronous reading, enabled by the `tokio` feature.
+//!
+//! Parsing is shared with the blocking reader: the header and offset table are read into memory,
+//! and then decoded exactly as `StrMap::read` would decode them.
+
+use std::future::Future;
+use std::io::{self, Cursor, SeekFrom};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt};
+
+use crate::{decode_header, detect, extract, Layout, StrMap, StrMapError, DETECT_LEN};
+
+impl StrMap {
+    /// Reads an index from an asynchronous source, detecting whether it was written with 32-bit
+    /// or 64-bit fields. This is the asynchronous counterpart of `StrMap::read`.
+    pub async fn read_async<T>(s: &mut T) -> Result<StrMap, StrMapError>
+        where T: AsyncRead + AsyncSeek + Unpin
+    {
+        read_index(s, None).await
+    }
+
+    /// Reads an index written in the given layout from an asynchronous source, bypassing
+    /// detection. This is the asynchronous counterpart of `StrMap::read_layout`.
+    pub async fn read_layout_async<T>(s: &mut T, layout: Layout) -> Result<StrMap, StrMapError>
+        where T: AsyncRead + AsyncSeek + Unpin
+    {
+        read_index(s, Some(layout)).await
+    }
+
+    /// Fetches the string at `idx` from the cookie text in `text`, reading only the bytes it
+    /// occupies. Resolves to `None` under the same conditions as `StrMap::record`.
+    ///
+    /// The returned future borrows only `text`, not the index.
+    pub fn record_async<'a, T>(&self, text: &'a mut T, idx: usize) -> impl Future<Output = io::Result<Option<Vec<u8>>>> + 'a
+        where T: AsyncRead + AsyncSeek + Unpin
+    {
+        let range = self.get(idx);
+        let delimiter = self.delimiter;
+
+        async move {
+            let (start, end) = match range {
+                Some(range) => range,
+                None => return Ok(None),
+            };
+            if start > text.seek(SeekFrom::End(0)).await? {
+                return Ok(None);
+            }
+
+            let mut buf = Vec::new();
+            text.seek(SeekFrom::Start(start)).await?;
+            text.take(end.saturating_sub(start)).read_to_end(&mut buf).await?;
+            Ok(extract(&buf, 0, buf.len() as u64, delimiter).map(<[u8]>::to_vec))
+        }
+    }
+}
+
+/// Reads the header and offset table starting at the current position, and no further, leaving
+/// the position just past the table as the blocking reader does.
+async fn read_index<T>(s: &mut T, layout: Option<Layout>) -> Result<StrMap, StrMapError>
+    where T: AsyncRead + AsyncSeek + Unpin
+{
+    let base = s.stream_position().await?;
+    let len = s.seek(SeekFrom::End(0)).await? - base;
+    s.seek(SeekFrom::Start(base)).await?;
+
+    let mut data = Vec::new();
+    (&mut *s).take(DETECT_LEN).read_to_end(&mut data).await?;
+    let (layout, endianness) = detect(&data, len, layout);
+    let header = decode_header(&data, layout, endianness).map_err(|e| e.offset_by(base))?;
+
+    // The count comes straight from the file, so the table is read up to its promised end
+    // rather than allocated up front.
+    let end = layout.header_len() + (header.count as u64 + 1) * layout.stride();
+    if end < data.len() as u64 {
+        data.truncate(end as usize);
+    } else {
+        (&mut *s).take(end - data.len() as u64).read_to_end(&mut data).await?;
+    }
+    s.seek(SeekFrom::Start(base + data.len() as u64)).await?;
+
+    StrMap::read_layout_endian(&mut Cursor::new(data), layout, endianness).map_err(|e| e.offset_by(base))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::Future;
+    use std::io::{self, Cursor, SeekFrom};
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+    use tokio::fs::File;
+    use tokio::io::{AsyncRead, AsyncSeek, AsyncSeekExt, ReadBuf};
+    use tokio::runtime::Builder;
+    use crate::{Layout, StrMap, StrMapError, DETECT_LEN};
+
+    static SAMPLE: &[u8] = include_bytes!("../sample.txt");
+    static SAMPLE_DAT: &[u8] = include_bytes!("../sample.txt.dat");
+    static SAMPLE_DAT_64: &[u8] = include_bytes!("../sample.txt-64.dat");
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        Builder::new_current_thread().build().unwrap().block_on(future)
+    }
+
+    fn matches_blocking(dat: &[u8]) {
+        let expected = StrMap::read(&mut Cursor::new(dat)).unwrap();
+        let map = block_on(StrMap::read_async(&mut Cursor::new(dat))).unwrap();
+
+        assert_eq!(expected.layout(), map.layout());
+        assert_eq!(expected.header(), map.header());
+        assert_eq!(expected.iter().collect::<Vec<_>>(), map.iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn reads_x86() {
+        matches_blocking(SAMPLE_DAT);
+    }
+
+    #[test]
+    fn reads_x64() {
+        matches_blocking(SAMPLE_DAT_64);
+    }
+
+    #[test]
+    fn reads_with_layout() {
+        let map = block_on(StrMap::read_layout_async(&mut Cursor::new(SAMPLE_DAT_64), Layout::X64)).unwrap();
+        assert_eq!(4, map.len());
+    }
+
+    // A real file completes its reads and seeks on a background thread, so the futures are polled
+    // more than once.
+    #[test]
+    fn reads_files() {
+        let runtime = Builder::new_current_thread().build().unwrap();
+        let mut dat = runtime.block_on(File::open(concat!(env!("CARGO_MANIFEST_DIR"), "/sample.txt.dat"))).unwrap();
+        let mut text = runtime.block_on(File::open(concat!(env!("CARGO_MANIFEST_DIR"), "/sample.txt"))).unwrap();
+
+        let map = runtime.block_on(StrMap::read_async(&mut dat)).unwrap();
+        assert_eq!(Some(SAMPLE_DAT.len() as u64), runtime.block_on(dat.stream_position()).ok());
+
+        let record = runtime.block_on(map.record_async(&mut text, 3)).unwrap();
+        assert_eq!(Some(b"To use with strfile\n\n".to_vec()), record);
+    }
+
+    #[test]
+    fn errors_report_original_positions() {
+        let mut dat = vec![0xff; 8];
+        dat.extend_from_slice(&SAMPLE_DAT[..SAMPLE_DAT.len() - 4]);
+        let mut cursor = Cursor::new(dat);
+        cursor.set_position(8);
+
+        match block_on(StrMap::read_async(&mut cursor)) {
+            Err(StrMapError::CountMismatch { position: 48, expected: 4, actual: 3 }) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn leaves_position_after_table() {
+        let mut dat = SAMPLE_DAT.to_vec();
+        dat.extend_from_slice(b"trailing");
+        let mut cursor = Cursor::new(dat);
+
+        block_on(StrMap::read_layout_async(&mut cursor, Layout::X86)).unwrap();
+        assert_eq!(SAMPLE_DAT.len() as u64, cursor.position());
+    }
+
+    #[test]
+    fn reads_only_the_index() {
+        struct CountReads<T> {
+            inner: T,
+            read: usize,
+        }
+
+        impl<T: AsyncRead + Unpin> AsyncRead for CountReads<T> {
+            fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context, buf: &mut ReadBuf) -> Poll<io::Result<()>> {
+                let before = buf.filled().len();
+                let result = Pin::new(&mut self.inner).poll_read(cx, buf);
+                self.read += buf.filled().len() - before;
+                result
+            }
+        }
+
+        impl<T: AsyncSeek + Unpin> AsyncSeek for CountReads<T> {
+            fn start_seek(mut self: Pin<&mut Self>, position: SeekFrom) -> io::Result<()> {
+                Pin::new(&mut self.inner).start_seek(position)
+            }
+
+            fn poll_complete(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<u64>> {
+                Pin::new(&mut self.inner).poll_complete(cx)
+            }
+        }
+
+        let mut dat = SAMPLE_DAT.to_vec();
+        dat.resize(1 << 20, b'x');
+        let mut source = CountReads { inner: Cursor::new(dat), read: 0 };
+
+        let map = block_on(StrMap::read_async(&mut source)).unwrap();
+        assert_eq!(4, map.len());
+        assert!(source.read <= SAMPLE_DAT.len() + DETECT_LEN as usize, "read {} bytes", source.read);
+    }
+
+    #[test]
+    fn fetches_records() {
+        let map = StrMap::read(&mut Cursor::new(SAMPLE_DAT)).unwrap();
+        let mut text = Cursor::new(SAMPLE);
+
+        for idx in 0..map.len() as usize {
+            let record = block_on(map.record_async(&mut text, idx)).unwrap();
+            assert_eq!(map.record(SAMPLE, idx).map(<[u8]>::to_vec), record);
+        }
+        assert_eq!(None, block_on(map.record_async(&mut text, 4)).unwrap());
+        assert_eq!(None, block_on(map.record_async(&mut Cursor::new(&SAMPLE[..10]), 2)).unwrap());
+    }
+}
@@ -0,0 +1,435 @@
+extern crate rand;
+extern crate strmap;
+
+use std::borrow::Cow;
+use std::env;
+use std::ffi::OsStr;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::thread;
+use std::time::Duration;
+
+mod getopt;
+
+use rand::Rng;
+use strmap::{write_matches, Collection, CookieFile, LengthFilter, Search, StrMapError};
+
+static USAGE: &str = "usage: fortune [-acefilosw] [-n length] [-m pattern] [[N%] file/directory/all]";
+
+/// Where to look for cookie files when `FORTUNE_PATH` is not set, in order of preference.
+static DEFAULT_DIRS: &[&str] = &[
+    "/usr/share/games/fortunes",
+    "/usr/share/games/fortune",
+    "/usr/share/fortune",
+    "/usr/local/share/games/fortunes",
+    "/usr/local/share/games/fortune",
+    "/opt/homebrew/share/games/fortunes",
+    "/opt/local/share/games/fortunes",
+];
+
+/// Characters per second a reader is assumed to manage, for `-w`.
+const CHARS_PER_SECOND: u64 = 20;
+
+/// The least time `-w` will wait, in seconds.
+const MIN_WAIT: u64 = 6;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum Offensive {
+    #[default]
+    Exclude,
+    Only,
+    Include,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Source {
+    percent: Option<u32>,
+    name: String,
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Options {
+    offensive: Offensive,
+    equal: bool,
+    list: bool,
+    short: bool,
+    long: bool,
+    length: Option<u32>,
+    pattern: Option<String>,
+    ignore_case: bool,
+    show_file: bool,
+    wait: bool,
+    sources: Vec<Source>,
+}
+
+impl Options {
+    /// Parses command line arguments the way fortune's getopt does.
+    fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Options, String> {
+        let mut options = Options::default();
+        let (flags, rest) = getopt::parse(args, "acefilm:n:osw")?;
+
+        for (flag, value) in flags {
+            let value = value.unwrap_or_default();
+            match flag {
+                'a' => options.offensive = Offensive::Include,
+                'o' => options.offensive = Offensive::Only,
+                'c' => options.show_file = true,
+                'e' => options.equal = true,
+                'f' => options.list = true,
+                'i' => options.ignore_case = true,
+                's' => {
+                    options.short = true;
+                    options.long = false;
+                }
+                'l' => {
+                    options.long = true;
+                    options.short = false;
+                }
+                'w' => options.wait = true,
+                'n' => options.length = Some(value.parse().map_err(|_| format!("invalid length: {}", value))?),
+                'm' => options.pattern = Some(value),
+                _ => unreachable!(),
+            }
+        }
+
+        options.sources = parse_sources(rest)?;
+        Ok(options)
+    }
+
+    fn filter(&self) -> LengthFilter {
+        let length = self.length.unwrap_or(LengthFilter::DEFAULT_LENGTH);
+        if self.short {
+            LengthFilter::Short(length)
+        } else if self.long {
+            LengthFilter::Long(length)
+        } else {
+            LengthFilter::Any
+        }
+    }
+}
+
+/// Pairs each file or directory with the percentage before it, written either as a separate
+/// argument (`30% foo`) or attached (`30%foo`).
+fn parse_sources(args: Vec<String>) -> Result<Vec<Source>, String> {
+    let mut sources = Vec::new();
+    let mut percent = None;
+
+    for arg in args {
+        let name = match split_percent(&arg) {
+            Some((value, name)) => {
+                if percent.is_some() {
+                    return Err(format!("percentages must precede files: {}", arg));
+                }
+                percent = Some(value.parse::<u32>().map_err(|_| format!("invalid percentage: {}", arg))?);
+                if name.is_empty() {
+                    continue;
+                }
+                name
+            }
+            None => &arg,
+        };
+
+        sources.push(Source {
+            percent: percent.take(),
+            name: name.to_owned(),
+        });
+    }
+
+    if percent.is_some() {
+        return Err("percentages must precede files".to_owned());
+    }
+    Ok(sources)
+}
+
+fn split_percent(arg: &str) -> Option<(&str, &str)> {
+    let idx = arg.find('%')?;
+    let (value, name) = (&arg[..idx], &arg[idx + 1..]);
+    if !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit()) {
+        Some((value, name))
+    } else {
+        None
+    }
+}
+
+/// The fortune directories to search, from `FORTUNE_PATH` if it is set.
+fn fortune_dirs() -> Vec<PathBuf> {
+    let dirs: Vec<PathBuf> = match env::var_os("FORTUNE_PATH") {
+        Some(path) => env::split_paths(&path).collect(),
+        None => DEFAULT_DIRS.iter().map(PathBuf::from).collect(),
+    };
+    dirs.into_iter().filter(|dir| dir.is_dir()).collect()
+}
+
+/// Offensive files live either in an `off` directory or beside the others with a `-o` suffix.
+fn is_offensive_name(path: &Path) -> bool {
+    path.file_name().and_then(OsStr::to_str).is_some_and(|name| name.ends_with("-o"))
+}
+
+/// Opens the cookie files directly inside `dir`, naming the directory in any error.
+fn dir_files(dir: &Path) -> io::Result<Vec<CookieFile>> {
+    Collection::read_dir(dir).map_err(|e| with_path(dir, e))
+}
+
+/// Opens a single cookie file, naming it in any error.
+fn open_file(path: &Path) -> io::Result<Vec<CookieFile>> {
+    CookieFile::open(path).map(|file| vec![file]).map_err(|e| with_path(path, e))
+}
+
+fn with_path(path: &Path, e: StrMapError) -> io::Error {
+    let e = io::Error::from(e);
+    io::Error::new(e.kind(), format!("{}: {}", path.display(), e))
+}
+
+/// The files in a fortune directory selected by `-a` and `-o`.
+fn default_files(dir: &Path, offensive: Offensive) -> io::Result<Vec<CookieFile>> {
+    let mut files: Vec<_> = dir_files(dir)?.into_iter().filter(|file| {
+        match offensive {
+            Offensive::Exclude => !is_offensive_name(file.path()),
+            Offensive::Only => is_offensive_name(file.path()),
+            Offensive::Include => true,
+        }
+    }).collect();
+
+    let off = dir.join("off");
+    if offensive != Offensive::Exclude && off.is_dir() {
+        files.extend(dir_files(&off)?);
+    }
+    Ok(files)
+}
+
+/// Opens the files named by one command line argument.
+fn resolve(name: &str, dirs: &[PathBuf], offensive: Offensive) -> io::Result<Vec<CookieFile>> {
+    if name == "all" {
+        let mut files = Vec::new();
+        for dir in dirs {
+            files.extend(default_files(dir, offensive)?);
+        }
+        return Ok(files);
+    }
+
+    let path = Path::new(name);
+    if path.is_dir() {
+        return dir_files(path);
+    }
+    if path.is_file() {
+        return open_file(path);
+    }
+
+    if path.is_relative() {
+        for dir in dirs {
+            let mut candidates = Vec::new();
+            if offensive != Offensive::Only {
+                candidates.push(dir.join(path));
+            }
+            if offensive != Offensive::Exclude {
+                candidates.push(dir.join("off").join(path));
+                candidates.push(dir.join(format!("{}-o", name)));
+            }
+
+            for candidate in candidates {
+                if candidate.is_dir() {
+                    return dir_files(&candidate);
+                }
+                if candidate.is_file() {
+                    return open_file(&candidate);
+                }
+            }
+        }
+    }
+
+    Err(io::Error::new(io::ErrorKind::NotFound, format!("{}: No such file or directory", name)))
+}
+
+fn build_collection(options: &Options) -> io::Result<Collection> {
+    let dirs = fortune_dirs();
+    let all = [Source {
+        percent: None,
+        name: "all".to_owned(),
+    }];
+    let sources = if options.sources.is_empty() { &all[..] } else { &options.sources[..] };
+
+    let mut collection = Collection::new();
+    collection.set_equal(options.equal);
+
+    for source in sources {
+        let files = resolve(&source.name, &dirs, options.offensive)?;
+
+        // A percentage given to a directory is shared by the files in it.
+        match source.percent {
+            Some(percent) => collection.add_group_with_percent(files, percent),
+            None => {
+                for file in files {
+                    collection.add(file);
+                }
+            }
+        }
+    }
+
+    if collection.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::NotFound, "No fortunes found"));
+    }
+    Ok(collection)
+}
+
+fn file_name(file: &CookieFile) -> String {
+    let path = file.path();
+    path.file_name().unwrap_or(path.as_os_str()).to_string_lossy().into_owned()
+}
+
+/// Picks a string from the collection, reporting inconsistent percentages rather than finding
+/// nothing.
+fn pick<'a, R: Rng>(
+    collection: &'a Collection,
+    filter: LengthFilter,
+    rng: &mut R,
+) -> io::Result<(&'a CookieFile, Cow<'a, [u8]>)> {
+    collection.random_filtered(filter, rng)?
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "No fortunes found"))
+}
+
+fn run(options: &Options) -> io::Result<bool> {
+    let collection = build_collection(options)?;
+    let filter = options.filter();
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+
+    if options.list {
+        let stderr = io::stderr();
+        let mut err = stderr.lock();
+        for (file, percent) in collection.probabilities()? {
+            writeln!(err, "{:6.2}% {}", percent, file.path().display())?;
+        }
+        return Ok(true);
+    }
+
+    if let Some(ref pattern) = options.pattern {
+        let search = Search::new(pattern, options.ignore_case)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e.to_string()))?;
+        let matches: Vec<_> = search.collection(&collection).into_iter()
+            .filter(|m| m.file().get(m.index()).is_some_and(|record| filter.matches(record.len())))
+            .collect();
+
+        write_matches(&mut out, &matches)?;
+        return Ok(!matches.is_empty());
+    }
+
+    let (file, record) = pick(&collection, filter, &mut rand::thread_rng())?;
+
+    if options.show_file {
+        writeln!(out, "({})", file_name(file))?;
+        out.write_all(&[file.map().delimiter(), b'\n'])?;
+    }
+    out.write_all(&record)?;
+    out.flush()?;
+
+    if options.wait {
+        let seconds = (record.len() as u64 / CHARS_PER_SECOND).max(MIN_WAIT);
+        thread::sleep(Duration::from_secs(seconds));
+    }
+    Ok(true)
+}
+
+fn main() {
+    let options = match Options::parse(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("fortune: {}", message);
+            eprintln!("{}", USAGE);
+            process::exit(1);
+        }
+    };
+
+    match run(&options) {
+        Ok(true) => (),
+        Ok(false) => process::exit(1),
+        Err(e) => {
+            eprintln!("fortune: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{build_collection, pick, Offensive, Options, Source};
+    use strmap::LengthFilter;
+
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        Options::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    fn source(percent: Option<u32>, name: &str) -> Source {
+        Source {
+            percent,
+            name: name.to_owned(),
+        }
+    }
+
+    #[test]
+    fn combined_flags() {
+        let options = parse(&["-ecw", "-o"]).unwrap();
+
+        assert!(options.equal && options.show_file && options.wait);
+        assert_eq!(Offensive::Only, options.offensive);
+        assert!(options.sources.is_empty());
+    }
+
+    #[test]
+    fn length_options() {
+        assert_eq!(LengthFilter::Short(160), parse(&["-s"]).unwrap().filter());
+        assert_eq!(LengthFilter::Long(40), parse(&["-sln", "40"]).unwrap().filter());
+        assert_eq!(LengthFilter::Short(10), parse(&["-s", "-n10"]).unwrap().filter());
+        assert_eq!(LengthFilter::Any, parse(&["-n", "10"]).unwrap().filter());
+        assert!(parse(&["-n", "many"]).is_err());
+    }
+
+    #[test]
+    fn pattern_takes_rest_of_argument() {
+        let options = parse(&["-im", "foo", "bar"]).unwrap();
+
+        assert!(options.ignore_case);
+        assert_eq!(Some("foo".to_owned()), options.pattern);
+        assert_eq!(vec![source(None, "bar")], options.sources);
+        assert!(parse(&["-m"]).is_err());
+    }
+
+    #[test]
+    fn percentages_attach_to_following_file() {
+        let options = parse(&["30%", "foo", "bar", "70%baz"]).unwrap();
+
+        assert_eq!(vec![source(Some(30), "foo"), source(None, "bar"), source(Some(70), "baz")], options.sources);
+        assert!(parse(&["30%"]).is_err());
+        assert!(parse(&["30%", "40%", "foo"]).is_err());
+    }
+
+    #[test]
+    fn options_end_at_first_file() {
+        let options = parse(&["foo", "-a"]).unwrap();
+
+        assert_eq!(Offensive::Exclude, options.offensive);
+        assert_eq!(vec![source(None, "foo"), source(None, "-a")], options.sources);
+    }
+
+    #[test]
+    fn unknown_flags_are_errors() {
+        assert!(parse(&["-z"]).is_err());
+    }
+
+    #[test]
+    fn percentage_errors_are_reported() {
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/collection");
+        let one = format!("{}/one", dir);
+        let two = format!("{}/two", dir);
+
+        let cases: &[(&[&str], &str)] = &[
+            (&["60%", &one], "No place to put residual probability (40%)"),
+            (&["60%", &one, "60%", &two], "Probabilities sum to 120%"),
+        ];
+        for &(args, message) in cases {
+            let collection = build_collection(&parse(args).unwrap()).unwrap();
+            let error = pick(&collection, LengthFilter::Any, &mut rand::thread_rng()).unwrap_err();
+            assert_eq!(message, error.to_string());
+        }
+    }
+}
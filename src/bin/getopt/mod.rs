@@ -0,0 +1,55 @@
+//! The command line parsing shared by the binaries.
+
+/// Each option given on a command line, in order, along with its value if it takes one.
+pub type Opts = Vec<(char, Option<String>)>;
+
+/// Parses command line arguments getopt-style, returning each option in order along with its
+/// value, followed by the remaining arguments.
+///
+/// `optstring` lists the option characters, each followed by a colon if it takes a value, as in
+/// getopt(3). Flags may be combined, and an option that takes a value takes it either attached
+/// or as the next argument. Options end at `--` or at the first argument that isn't one.
+pub fn parse<I: IntoIterator<Item = String>>(
+    args: I,
+    optstring: &str,
+) -> Result<(Opts, Vec<String>), String> {
+    let mut args = args.into_iter();
+    let mut options = Vec::new();
+
+    let mut rest = Vec::new();
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            rest.extend(args.by_ref());
+            break;
+        }
+        if !arg.starts_with('-') || arg == "-" {
+            rest.push(arg);
+            rest.extend(args.by_ref());
+            break;
+        }
+
+        let flags = &arg[1..];
+        for (idx, flag) in flags.char_indices() {
+            let takes_value = match optstring.find(flag) {
+                Some(pos) if flag != ':' => optstring[pos + flag.len_utf8()..].starts_with(':'),
+                _ => return Err(format!("invalid option -- {}", flag)),
+            };
+
+            if !takes_value {
+                options.push((flag, None));
+                continue;
+            }
+
+            let attached = &flags[idx + flag.len_utf8()..];
+            let value = if attached.is_empty() {
+                args.next().ok_or_else(|| format!("option requires an argument -- {}", flag))?
+            } else {
+                attached.to_owned()
+            };
+            options.push((flag, Some(value)));
+            break;
+        }
+    }
+
+    Ok((options, rest))
+}
@@ -0,0 +1,207 @@
+extern crate rand;
+extern crate strmap;
+
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::process;
+
+mod getopt;
+
+use strmap::{Endianness, Layout, StrMap, StrMapBuilder};
+
+static USAGE: &str = "usage: strfile [-Ciorsx] [-c char] [-e big|little] [-l x86|x64|x64-wide] source_file [output_file]";
+
+#[derive(Debug, PartialEq, Eq)]
+struct Options {
+    delimiter: u8,
+    ignore_case: bool,
+    ordered: bool,
+    random: bool,
+    silent: bool,
+    rotated: bool,
+    comments: bool,
+    layout: Layout,
+    endianness: Endianness,
+    source: PathBuf,
+    output: Option<PathBuf>,
+}
+
+impl Options {
+    /// Parses command line arguments the way strfile's getopt does.
+    fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Options, String> {
+        let mut options = Options {
+            delimiter: b'%',
+            ignore_case: false,
+            ordered: false,
+            random: false,
+            silent: false,
+            rotated: false,
+            comments: false,
+            layout: Layout::X86,
+            endianness: Endianness::Big,
+            source: PathBuf::new(),
+            output: None,
+        };
+        let (flags, rest) = getopt::parse(args, "Cc:e:il:orsx")?;
+
+        for (flag, value) in flags {
+            let value = value.unwrap_or_default();
+            match flag {
+                'C' => options.comments = true,
+                'i' => options.ignore_case = true,
+                'o' => options.ordered = true,
+                'r' => options.random = true,
+                's' => options.silent = true,
+                'x' => options.rotated = true,
+                'c' => options.delimiter = parse_delimiter(&value)?,
+                'e' => options.endianness = parse_endianness(&value)?,
+                'l' => options.layout = parse_layout(&value)?,
+                _ => unreachable!(),
+            }
+        }
+
+        let mut rest = rest.into_iter();
+        options.source = rest.next().ok_or("no input file name")?.into();
+        options.output = rest.next().map(PathBuf::from);
+        if rest.next().is_some() {
+            return Err("too many arguments".to_owned());
+        }
+
+        Ok(options)
+    }
+}
+
+fn parse_delimiter(value: &str) -> Result<u8, String> {
+    match value.as_bytes() {
+        &[delimiter] if delimiter.is_ascii() => Ok(delimiter),
+        _ => Err(format!("delimiter must be a single ASCII character: {}", value)),
+    }
+}
+
+fn parse_layout(value: &str) -> Result<Layout, String> {
+    Layout::from_name(value).ok_or_else(|| format!("unknown layout: {}", value))
+}
+
+fn parse_endianness(value: &str) -> Result<Endianness, String> {
+    Endianness::from_name(value).ok_or_else(|| format!("unknown byte order: {}", value))
+}
+
+fn plural(count: u32) -> &'static str {
+    if count == 1 { "" } else { "s" }
+}
+
+/// Builds the index for `text` as the options describe. As in strfile, `-o` takes precedence
+/// over `-r`.
+fn index(options: &Options, text: &[u8]) -> io::Result<StrMap> {
+    let builder = StrMapBuilder::new()
+        .delimiter(options.delimiter)
+        .ordered(options.ordered)
+        .ignore_case(options.ignore_case)
+        .rotated(options.rotated)
+        .comments(options.comments);
+
+    let mut map = builder.build_bytes(text)?;
+    if options.random && !options.ordered {
+        map.shuffle(&mut rand::thread_rng());
+    }
+    Ok(map)
+}
+
+fn run(options: &Options) -> io::Result<()> {
+    let text = fs::read(&options.source)?;
+    let map = index(options, &text)?;
+
+    let output = options.output.clone().unwrap_or_else(|| strmap::dat_path(&options.source));
+    let mut out = BufWriter::new(File::create(&output)?);
+    map.write_layout_endian(&mut out, options.layout, options.endianness)?;
+    out.flush()?;
+
+    if !options.silent {
+        println!("\"{}\" created", output.display());
+        if map.len() == 1 {
+            println!("There was 1 string");
+        } else {
+            println!("There were {} strings", map.len());
+        }
+        println!("Longest string: {} byte{}", map.longest(), plural(map.longest()));
+        println!("Shortest string: {} byte{}", map.shortest(), plural(map.shortest()));
+    }
+    Ok(())
+}
+
+fn main() {
+    let options = match Options::parse(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("strfile: {}", message);
+            eprintln!("{}", USAGE);
+            process::exit(1);
+        }
+    };
+
+    if let Err(e) = run(&options) {
+        eprintln!("strfile: {}: {}", options.source.display(), e);
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use strmap::{Endianness, Layout};
+    use super::{index, Options};
+
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        Options::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn defaults() {
+        let options = parse(&["fortunes"]).unwrap();
+
+        assert_eq!(b'%', options.delimiter);
+        assert_eq!(Layout::X86, options.layout);
+        assert_eq!(Endianness::Big, options.endianness);
+        assert!(!options.ordered && !options.random && !options.silent && !options.rotated && !options.comments);
+        assert_eq!(PathBuf::from("fortunes"), options.source);
+        assert_eq!(None, options.output);
+    }
+
+    #[test]
+    fn combined_flags() {
+        let options = parse(&["-oisxC", "-c#", "-l", "x64", "fortunes", "out.dat"]).unwrap();
+
+        assert!(options.ordered && options.ignore_case && options.silent && options.rotated && options.comments);
+        assert_eq!(b'#', options.delimiter);
+        assert_eq!(Layout::X64, options.layout);
+        assert_eq!(Some(PathBuf::from("out.dat")), options.output);
+    }
+
+    #[test]
+    fn byte_order_can_be_chosen() {
+        let options = parse(&["-elittle", "-l", "x64", "fortunes"]).unwrap();
+        assert_eq!(Endianness::Little, options.endianness);
+    }
+
+    #[test]
+    fn ordered_takes_precedence_over_random() {
+        let options = parse(&["-or", "fortunes"]).unwrap();
+        assert!(options.ordered && options.random);
+
+        let map = index(&options, b"c\n%\nb\n%\na\n%\nd\n").unwrap();
+        assert!(map.is_ordered() && !map.is_random());
+        assert_eq!(vec![(8, 12), (4, 8), (0, 4), (12, 14)], map.iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn bad_arguments_are_errors() {
+        assert!(parse(&[]).is_err());
+        assert!(parse(&["-c", "ab", "fortunes"]).is_err());
+        assert!(parse(&["-l", "x128", "fortunes"]).is_err());
+        assert!(parse(&["-e", "middle", "fortunes"]).is_err());
+        assert!(parse(&["-q", "fortunes"]).is_err());
+        assert!(parse(&["a", "b", "c"]).is_err());
+    }
+}
@@ -0,0 +1,145 @@
+extern crate strmap;
+
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+
+mod getopt;
+
+use strmap::{DumpFormat, Layout, StrMap};
+
+static USAGE: &str = "usage: strinfo [-aj] [-l x86|x64|x64-wide] [-t text_file] index_file";
+
+#[derive(Debug, PartialEq, Eq)]
+struct Options {
+    format: DumpFormat,
+    annotate: bool,
+    text: Option<PathBuf>,
+    layout: Option<Layout>,
+    index: PathBuf,
+}
+
+impl Options {
+    /// Parses command line arguments getopt-style.
+    fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Options, String> {
+        let mut options = Options {
+            format: DumpFormat::Text,
+            annotate: false,
+            text: None,
+            layout: None,
+            index: PathBuf::new(),
+        };
+        let (flags, rest) = getopt::parse(args, "ajl:t:")?;
+
+        for (flag, value) in flags {
+            let value = value.unwrap_or_default();
+            match flag {
+                'a' => options.annotate = true,
+                'j' => options.format = DumpFormat::Json,
+                'l' => {
+                    let layout = Layout::from_name(&value).ok_or_else(|| format!("unknown layout: {}", value))?;
+                    options.layout = Some(layout);
+                }
+                't' => options.text = Some(value.into()),
+                _ => unreachable!(),
+            }
+        }
+
+        let mut rest = rest.into_iter();
+        options.index = rest.next().ok_or("no index file name")?.into();
+        if rest.next().is_some() {
+            return Err("too many arguments".to_owned());
+        }
+
+        Ok(options)
+    }
+
+    /// The text file to annotate offsets from: the one given with `-t`, or with `-a` the one the
+    /// index is named after.
+    fn text_path(&self) -> Option<PathBuf> {
+        if self.text.is_some() {
+            return self.text.clone();
+        }
+        if !self.annotate {
+            return None;
+        }
+
+        let index = self.index.to_str()?;
+        Some(Path::new(index.strip_suffix(".dat").unwrap_or(index)).to_owned())
+    }
+}
+
+fn run(options: &Options) -> io::Result<()> {
+    let mut source = BufReader::new(File::open(&options.index)?);
+    let map = match options.layout {
+        Some(layout) => StrMap::read_layout(&mut source, layout)?,
+        None => StrMap::read(&mut source)?,
+    };
+
+    let text = match options.text_path() {
+        Some(path) => Some(fs::read(&path).map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))?),
+        None => None,
+    };
+
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    map.dump(&mut out, options.format, text.as_deref())?;
+    out.flush()
+}
+
+fn main() {
+    let options = match Options::parse(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("strinfo: {}", message);
+            eprintln!("{}", USAGE);
+            process::exit(1);
+        }
+    };
+
+    if let Err(e) = run(&options) {
+        eprintln!("strinfo: {}: {}", options.index.display(), e);
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use strmap::{DumpFormat, Layout};
+    use super::Options;
+
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        Options::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn defaults() {
+        let options = parse(&["fortunes.dat"]).unwrap();
+
+        assert_eq!(DumpFormat::Text, options.format);
+        assert_eq!(None, options.layout);
+        assert_eq!(None, options.text_path());
+    }
+
+    #[test]
+    fn annotation_uses_text_beside_index() {
+        assert_eq!(Some(PathBuf::from("fortunes")), parse(&["-aj", "fortunes.dat"]).unwrap().text_path());
+        assert_eq!(Some(PathBuf::from("other")), parse(&["-t", "other", "fortunes.dat"]).unwrap().text_path());
+    }
+
+    #[test]
+    fn layout_can_be_forced() {
+        assert_eq!(Some(Layout::X64Wide), parse(&["-lx64-wide", "fortunes.dat"]).unwrap().layout);
+        assert!(parse(&["-l", "x32", "fortunes.dat"]).is_err());
+    }
+
+    #[test]
+    fn bad_arguments_are_errors() {
+        assert!(parse(&[]).is_err());
+        assert!(parse(&["-q", "fortunes.dat"]).is_err());
+        assert!(parse(&["a.dat", "b.dat"]).is_err());
+    }
+}
@@ -0,0 +1,356 @@
+use rand::Rng;
+
+use crate::{check_offsets, decode_header, decode_offset, extract, Endianness, Header, Layout, StrFlags, StrMapError};
+
+/// A view of an index held entirely in memory, such as a byte slice or a memory-mapped file.
+///
+/// Header fields are decoded once; offsets are decoded from the underlying bytes each time they
+/// are requested, so nothing is copied and the offset table is never allocated.
+#[cfg_attr(feature = "std", doc = r#"
+```no_run
+# extern crate memmap2;
+# extern crate strmap;
+# fn main() -> Result<(), Box<dyn std::error::Error>> {
+let file = std::fs::File::open("/usr/share/games/fortunes/fortunes.dat")?;
+let dat = unsafe { memmap2::Mmap::map(&file)? };
+let map = strmap::StrMapRef::new(&dat)?;
+println!("{} strings", map.len());
+# Ok(())
+# }
+```"#)]
+#[derive(Debug, Clone, Copy)]
+pub struct StrMapRef<'a> {
+    header: Header,
+    layout: Layout,
+    endianness: Endianness,
+    table: &'a [u8],
+}
+
+impl<'a> StrMapRef<'a> {
+    /// Parses the index in `data`, detecting its layout and byte order.
+    pub fn new(data: &'a [u8]) -> Result<StrMapRef<'a>, StrMapError> {
+        let layout = Layout::detect(data, data.len() as u64);
+        StrMapRef::with_layout(data, layout)
+    }
+
+    /// Parses the index in `data`, which was written in the given layout, detecting its byte
+    /// order.
+    pub fn with_layout(data: &'a [u8], layout: Layout) -> Result<StrMapRef<'a>, StrMapError> {
+        let endianness = Endianness::detect(data, data.len() as u64, layout);
+        StrMapRef::with_layout_endian(data, layout, endianness)
+    }
+
+    /// Parses the index in `data`, which was written in the given layout and byte order.
+    pub fn with_layout_endian(
+        data: &'a [u8],
+        layout: Layout,
+        endianness: Endianness,
+    ) -> Result<StrMapRef<'a>, StrMapError> {
+        let header = decode_header(data, layout, endianness)?;
+        let start = layout.header_len();
+
+        let stride = layout.stride();
+        let available = (data.len() as u64).saturating_sub(start) / stride;
+        if available < header.count as u64 + 1 {
+            return Err(StrMapError::CountMismatch {
+                position: data.len() as u64,
+                expected: header.count,
+                actual: available.saturating_sub(1) as u32,
+            });
+        }
+
+        let table = &data[start as usize..(start + (header.count as u64 + 1) * stride) as usize];
+        let map = StrMapRef {
+            header,
+            layout,
+            endianness,
+            table,
+        };
+
+        check_offsets(header.count as usize + 1, |idx| map.offset(idx), header.flags, |idx| {
+            start + idx as u64 * stride
+        })?;
+
+        Ok(map)
+    }
+
+    /// The layout of the index.
+    pub fn layout(&self) -> Layout {
+        self.layout
+    }
+
+    /// The byte order of the index.
+    pub fn endianness(&self) -> Endianness {
+        self.endianness
+    }
+
+    /// The header of the index.
+    pub fn header(&self) -> Header {
+        self.header
+    }
+
+    /// The strfile version recorded in the header.
+    pub fn version(&self) -> u32 {
+        self.header.version
+    }
+
+    /// The number of strings contained in the mapped file.
+    pub fn len(&self) -> u32 {
+        self.header.count
+    }
+
+    /// Whether the mapped file contains no strings at all.
+    pub fn is_empty(&self) -> bool {
+        self.header.count == 0
+    }
+
+    /// The longest string contained in the mapped file.
+    pub fn longest(&self) -> u32 {
+        self.header.longest
+    }
+
+    /// The shortest string contained in the mapped file.
+    pub fn shortest(&self) -> u32 {
+        self.header.shortest
+    }
+
+    /// The delimiter used in the mapped file.
+    pub fn delimiter(&self) -> u8 {
+        self.header.delimiter
+    }
+
+    /// Whether the index for this file is randomized.
+    pub fn is_random(&self) -> bool {
+        self.header.flags.contains(StrFlags::STR_RANDOM)
+    }
+
+    /// Whether the index for this file is sorted.
+    pub fn is_ordered(&self) -> bool {
+        self.header.flags.contains(StrFlags::STR_ORDERED)
+    }
+
+    /// Whether the contents of this file have been rotated via rot13.
+    pub fn is_rotated(&self) -> bool {
+        self.header.flags.contains(StrFlags::STR_ROTATED)
+    }
+
+    /// Whether this file contains comment lines, which begin with two delimiters.
+    pub fn has_comments(&self) -> bool {
+        self.header.flags.contains(StrFlags::STR_COMMENTS)
+    }
+
+    /// Returns an iterator over the string offsets contained in this index.
+    pub fn iter(&self) -> StrMapRefIter<'a> {
+        StrMapRefIter {
+            map: *self,
+            idx: 0,
+        }
+    }
+
+    /// Returns the offsets of the string at `idx`, if there is one.
+    ///
+    /// In an ordered or randomized index, the string physically following this one can't be found
+    /// without scanning the whole table, so the end offset given is the end of the text, as in
+    /// fortune(6). `record` still stops at the delimiter line.
+    pub fn get(&self, idx: usize) -> Option<(u64, u64)> {
+        let count = self.header.count as usize;
+        if idx >= count {
+            return None;
+        }
+
+        let sequential = !self.header.flags.intersects(StrFlags::STR_RANDOM | StrFlags::STR_ORDERED);
+        let end = if sequential { idx + 1 } else { count };
+        Some((self.offset(idx), self.offset(end)))
+    }
+
+    /// Picks the offsets of a string uniformly at random, or `None` if the index is empty.
+    pub fn random<R: Rng + ?Sized>(&self, rng: &mut R) -> Option<(u64, u64)> {
+        if self.header.count == 0 {
+            return None;
+        }
+        self.get(rng.gen_range(0..self.header.count as usize))
+    }
+
+    /// Extracts the string at `idx` from `text`, the cookie file described by this index, without
+    /// its delimiter line.
+    pub fn record<'b>(&self, text: &'b [u8], idx: usize) -> Option<&'b [u8]> {
+        let (start, end) = self.get(idx)?;
+        extract(text, start, end, self.header.delimiter)
+    }
+
+    /// Decodes entry `idx` of the offset table, which must exist.
+    fn offset(&self, idx: usize) -> u64 {
+        let stride = self.layout.stride() as usize;
+        decode_offset(&self.table[idx * stride..(idx + 1) * stride], self.layout, self.endianness)
+    }
+}
+
+impl<'a> IntoIterator for &StrMapRef<'a> {
+    type Item = (u64, u64);
+    type IntoIter = StrMapRefIter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+pub struct StrMapRefIter<'a> {
+    map: StrMapRef<'a>,
+    idx: usize,
+}
+
+impl<'a> Iterator for StrMapRefIter<'a> {
+    type Item = (u64, u64);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let pair = self.map.get(self.idx)?;
+        self.idx += 1;
+        Some(pair)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "std")]
+    use std::io::Cursor;
+    #[cfg(feature = "std")]
+    use crate::{Endianness, StrMap};
+    use crate::{Layout, StrMapError};
+    use super::StrMapRef;
+
+    static SAMPLE: &[u8] = include_bytes!("../sample.txt");
+    static SAMPLE_DAT: &[u8] = include_bytes!("../sample.txt.dat");
+    static SAMPLE_DAT_64: &[u8] = include_bytes!("../sample.txt-64.dat");
+
+    #[cfg(feature = "std")]
+    fn matches_owned(dat: &[u8]) {
+        let owned = StrMap::read(&mut Cursor::new(dat)).unwrap();
+        let borrowed = StrMapRef::new(dat).unwrap();
+
+        assert_eq!(owned.len(), borrowed.len());
+        assert_eq!(owned.longest(), borrowed.longest());
+        assert_eq!(owned.shortest(), borrowed.shortest());
+        assert_eq!(owned.delimiter(), borrowed.delimiter());
+        assert_eq!(owned.layout(), borrowed.layout());
+        assert_eq!(owned.endianness(), borrowed.endianness());
+        assert_eq!(owned.iter().collect::<Vec<_>>(), borrowed.iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn x86_matches_owned() {
+        matches_owned(SAMPLE_DAT);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn x64_matches_owned() {
+        matches_owned(SAMPLE_DAT_64);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn ordered_and_shuffled_match_owned() {
+        use rand::rngs::StdRng;
+        use rand::SeedableRng;
+
+        let text = b"d\n%\nbb\n%\n%\nccc\n%\na\n%\n";
+        let ordered = crate::StrMapBuilder::new().ordered(true).build_bytes(text).unwrap();
+        let mut shuffled = crate::StrMapBuilder::new().build_bytes(text).unwrap();
+        shuffled.shuffle(&mut StdRng::seed_from_u64(7));
+
+        for map in &[ordered, shuffled] {
+            let mut dat = Vec::new();
+            map.write(&mut dat).unwrap();
+            let borrowed = StrMapRef::new(&dat).unwrap();
+
+            assert_eq!(map.header(), borrowed.header());
+            for idx in 0..map.len() as usize {
+                let (start, end) = borrowed.get(idx).unwrap();
+                assert_eq!(map.get(idx).map(|(start, _)| start), Some(start));
+                assert_eq!(text.len() as u64, end);
+                assert_eq!(map.record(text, idx), borrowed.record(text, idx));
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn little_endian_matches_owned() {
+        let mut dat = Vec::new();
+        let map = StrMap::read(&mut Cursor::new(SAMPLE_DAT)).unwrap();
+        map.write_layout_endian(&mut dat, Layout::X86, Endianness::Little).unwrap();
+
+        matches_owned(&dat);
+        assert_eq!(Endianness::Little, StrMapRef::new(&dat).unwrap().endianness());
+    }
+
+    #[test]
+    fn reads_64bit_offsets() {
+        // An x64-wide header for a single string running from 0 to 5,000,000,000.
+        let mut dat = vec![0; 48];
+        for &(idx, value) in &[(0, 2u64), (1, 1), (2, 21), (3, 21)] {
+            dat[idx * 8..idx * 8 + 8].copy_from_slice(&value.to_be_bytes());
+        }
+        dat[40] = b'%';
+        dat.extend_from_slice(&0u64.to_be_bytes());
+        dat.extend_from_slice(&5_000_000_000u64.to_be_bytes());
+
+        let map = StrMapRef::new(&dat).unwrap();
+        assert_eq!(Layout::X64Wide, map.layout());
+        assert_eq!(Some((0, 5_000_000_000)), map.get(0));
+    }
+
+    #[test]
+    fn extracts_records() {
+        let map = StrMapRef::new(SAMPLE_DAT).unwrap();
+        assert_eq!(Some(&b"A known sample file\n"[..]), map.record(SAMPLE, 2));
+    }
+
+    #[test]
+    fn rejects_corrupt_offsets() {
+        let mut dat = SAMPLE_DAT.to_vec();
+        dat[35] = 0x01;
+
+        match StrMapRef::new(&dat) {
+            Err(StrMapError::OffsetOutOfOrder { position: 32, .. }) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn truncated_header_is_an_error() {
+        match StrMapRef::with_layout(&SAMPLE_DAT_64[..36], Layout::X64) {
+            Err(StrMapError::TruncatedHeader { position: 40, expected: 48, actual: 36 }) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn ordered_records_end_at_delimiter() {
+        let mut map = StrMap::read(&mut Cursor::new(SAMPLE_DAT)).unwrap();
+        map.sort(SAMPLE, false);
+        let mut dat = Vec::new();
+        map.write(&mut dat).unwrap();
+
+        let map = StrMapRef::new(&dat).unwrap();
+        assert!(map.is_ordered());
+        assert_eq!(Some((39, 82)), map.get(0));
+        assert_eq!(Some(&b"A known sample file\n"[..]), map.record(SAMPLE, 0));
+    }
+
+    #[test]
+    fn works_over_memory_map() {
+        use memmap2::Mmap;
+        use std::fs::File;
+        use std::path::Path;
+
+        let file = File::open(Path::new(env!("CARGO_MANIFEST_DIR")).join("sample.txt.dat")).unwrap();
+        let dat = unsafe { Mmap::map(&file).unwrap() };
+        let map = StrMapRef::new(&dat).unwrap();
+
+        assert_eq!(4, map.len());
+        assert_eq!(Some((61, 82)), map.get(3));
+    }
+}
@@ -0,0 +1,302 @@
+use std::io;
+
+use crate::scan;
+use crate::{Endianness, Layout, StrFlags, StrMap};
+
+/// The strfile version stamped on indexes produced by the builder.
+const VERSION: u32 = 2;
+
+/// Builds a `StrMap` from the text of a cookie file.
+///
+/// The text is scanned exactly the way strfile scans it: a string ends at any line consisting of
+/// the delimiter byte alone (or at the end of the file), and empty strings are skipped without
+/// producing an offset.
+#[derive(Debug, Clone)]
+pub struct StrMapBuilder {
+    delimiter: u8,
+    flags: StrFlags,
+    ignore_case: bool,
+}
+
+impl StrMapBuilder {
+    /// Creates a builder using strfile's default delimiter, `%`.
+    pub fn new() -> StrMapBuilder {
+        StrMapBuilder {
+            delimiter: b'%',
+            flags: StrFlags::empty(),
+            ignore_case: false,
+        }
+    }
+
+    /// Sets the byte used to separate strings, like `strfile -c`.
+    pub fn delimiter(mut self, delimiter: u8) -> StrMapBuilder {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Marks the text as rot13-encoded, like `strfile -x`. The text itself is not altered.
+    pub fn rotated(mut self, rotated: bool) -> StrMapBuilder {
+        self.flags.set(StrFlags::STR_ROTATED, rotated);
+        self
+    }
+
+    /// Marks the text as containing comment lines, like `strfile -C`. As in strfile, comments are
+    /// indexed as part of the strings around them; `CookieFile` leaves them out when reading.
+    pub fn comments(mut self, comments: bool) -> StrMapBuilder {
+        self.flags.set(StrFlags::STR_COMMENTS, comments);
+        self
+    }
+
+    /// Sorts the strings alphabetically, like `strfile -o`. See `StrMap::sort`.
+    pub fn ordered(mut self, ordered: bool) -> StrMapBuilder {
+        self.flags.set(StrFlags::STR_ORDERED, ordered);
+        self
+    }
+
+    /// Ignores case when sorting, like `strfile -i`. Has no effect unless `ordered` is set.
+    pub fn ignore_case(mut self, ignore_case: bool) -> StrMapBuilder {
+        self.ignore_case = ignore_case;
+        self
+    }
+
+    /// Scans the cookie text provided by `s` and returns the resulting index.
+    pub fn build<T: io::BufRead>(&self, s: &mut T) -> io::Result<StrMap> {
+        // Sorting means comparing strings, so an ordered index needs to hang on to the text.
+        let ordered = self.flags.contains(StrFlags::STR_ORDERED);
+        let mut text = Vec::new();
+
+        let mut strings = Strings::new();
+        let mut line = Vec::new();
+        let mut pos = 0;
+
+        loop {
+            line.clear();
+            let read = s.read_until(b'\n', &mut line)?;
+            pos += read as u64;
+            if ordered {
+                text.extend_from_slice(&line);
+            }
+
+            if read == 0 || self.is_delimiter(&line) {
+                strings.end(pos, read as u64)?;
+            }
+
+            if read == 0 {
+                break;
+            }
+        }
+
+        Ok(self.finish(strings, &text))
+    }
+
+    /// Scans cookie text that is already in memory and returns the resulting index, exactly as
+    /// `build` would.
+    ///
+    /// Delimiter lines are found with a vectorized search rather than line by line, and with the
+    /// `rayon` feature, very large texts are searched in parallel.
+    pub fn build_bytes(&self, text: &[u8]) -> io::Result<StrMap> {
+        let mut strings = Strings::new();
+        for pos in scan::delimiter_lines(text, self.delimiter) {
+            strings.end(pos, 2)?;
+        }
+        strings.end(text.len() as u64, 0)?;
+
+        Ok(self.finish(strings, text))
+    }
+
+    /// Assembles the index once every string has been found. `text` is only needed for sorting.
+    fn finish(&self, strings: Strings, text: &[u8]) -> StrMap {
+        let shortest = if strings.offsets.is_empty() { 0 } else { strings.shortest };
+        let mut map = StrMap {
+            version: VERSION,
+            count: strings.offsets.len() as u32,
+            longest: strings.longest,
+            shortest,
+            flags: self.flags,
+            delimiter: self.delimiter,
+            layout: Layout::X86,
+            endianness: Endianness::Big,
+            offsets: strings.offsets,
+        };
+
+        if self.flags.contains(StrFlags::STR_ORDERED) {
+            map.sort(text, self.ignore_case);
+        }
+        map
+    }
+
+    fn is_delimiter(&self, line: &[u8]) -> bool {
+        line == [self.delimiter, b'\n']
+    }
+}
+
+impl Default for StrMapBuilder {
+    fn default() -> StrMapBuilder {
+        StrMapBuilder::new()
+    }
+}
+
+/// The strings found so far, along with their lengths.
+struct Strings {
+    offsets: Vec<(u64, u64)>,
+    longest: u32,
+    shortest: u32,
+
+    // `last` is where the most recent delimiter line ended, whereas `start` is the last offset
+    // actually recorded. The two differ after an empty string, which strfile folds into the
+    // string that follows it.
+    last: u64,
+    start: u64,
+}
+
+impl Strings {
+    fn new() -> Strings {
+        Strings {
+            offsets: Vec::new(),
+            longest: 0,
+            shortest: u32::MAX,
+            last: 0,
+            start: 0,
+        }
+    }
+
+    /// Ends the current string at `pos`, just past the `delimiter_len` bytes of its delimiter
+    /// line, or at the end of the text with no delimiter line at all.
+    fn end(&mut self, pos: u64, delimiter_len: u64) -> io::Result<()> {
+        let length = to_length(pos - self.last - delimiter_len, self.last)?;
+        self.last = pos;
+
+        if length > 0 {
+            self.offsets.push((self.start, pos));
+            self.start = pos;
+            self.longest = self.longest.max(length);
+            self.shortest = self.shortest.min(length);
+        }
+        Ok(())
+    }
+}
+
+/// Checks that the string at `start` is short enough for the header to record its length.
+fn to_length(length: u64, start: u64) -> io::Result<u32> {
+    if length > u32::MAX as u64 {
+        return Err(io::Error::other(
+            format!("String at offset {} is too long to be indexed: {} bytes", start, length)
+        ));
+    }
+    Ok(length as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use super::StrMapBuilder;
+
+    static SAMPLE: &str = include_str!("../sample.txt");
+    static SAMPLE_DAT: &[u8] = include_bytes!("../sample.txt.dat");
+
+    #[test]
+    fn builds_sample_header() {
+        let map = StrMapBuilder::new().build(&mut SAMPLE.as_bytes()).unwrap();
+
+        assert_eq!(4, map.len());
+        assert_eq!(21, map.longest());
+        assert_eq!(17, map.shortest());
+        assert_eq!(vec![(0, 19), (19, 39), (39, 61), (61, 82)], map.iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn output_matches_strfile() {
+        let map = StrMapBuilder::new().build(&mut SAMPLE.as_bytes()).unwrap();
+        let mut output = Vec::new();
+        map.write(&mut output).unwrap();
+
+        assert_eq!(SAMPLE_DAT, &*output);
+    }
+
+    #[test]
+    fn output_can_be_read() {
+        let map = StrMapBuilder::new().build(&mut SAMPLE.as_bytes()).unwrap();
+        let mut output = Cursor::new(Vec::new());
+        map.write(&mut output).unwrap();
+        output.set_position(0);
+
+        let read = crate::StrMap::read(&mut output).unwrap();
+        assert_eq!(map.iter().collect::<Vec<_>>(), read.iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn empty_strings_are_skipped() {
+        let text = "a\n%\n%\nbc\n%\n";
+        let map = StrMapBuilder::new().build(&mut text.as_bytes()).unwrap();
+
+        assert_eq!(2, map.len());
+        assert_eq!(3, map.longest());
+        assert_eq!(2, map.shortest());
+        assert_eq!(vec![(0, 4), (4, 11)], map.iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn rotated_sets_flag() {
+        let map = StrMapBuilder::new().rotated(true).build(&mut SAMPLE.as_bytes()).unwrap();
+        assert!(map.is_rotated());
+    }
+
+    #[test]
+    fn comments_set_flag_without_changing_offsets() {
+        let text = "%% header\na\n%\nbc\n%\n";
+        let plain = StrMapBuilder::new().build(&mut text.as_bytes()).unwrap();
+        let commented = StrMapBuilder::new().comments(true).build(&mut text.as_bytes()).unwrap();
+
+        assert!(commented.has_comments() && !plain.has_comments());
+        assert_eq!(plain.iter().collect::<Vec<_>>(), commented.iter().collect::<Vec<_>>());
+
+        let mut output = Vec::new();
+        commented.write(&mut output).unwrap();
+        assert_eq!(8, output[19]);
+    }
+
+    #[test]
+    fn bytes_build_the_same_index() {
+        let texts: &[&[u8]] = &[
+            SAMPLE.as_bytes(),
+            b"",
+            b"%\n",
+            b"%\n%\na\n%\n%\nbc\n%",
+            b"no trailing newline",
+            b"a\n%\n\n%\n\n",
+            b"%\n%\n%\n",
+            b"a\n%\n%\n%\nb\n%%\n%\r\n%\n",
+        ];
+
+        for &text in texts {
+            for &ordered in &[false, true] {
+                let builder = StrMapBuilder::new().ordered(ordered);
+                let streamed = builder.build(&mut &text[..]).unwrap();
+                let in_memory = builder.build_bytes(text).unwrap();
+
+                assert_eq!(streamed.header(), in_memory.header());
+                assert_eq!(streamed.iter().collect::<Vec<_>>(), in_memory.iter().collect::<Vec<_>>());
+            }
+        }
+    }
+
+    #[test]
+    fn newline_delimiter_never_ends_a_string() {
+        let text = b"a\n\n\n\nb\n";
+        let builder = StrMapBuilder::new().delimiter(b'\n');
+        let streamed = builder.build(&mut &text[..]).unwrap();
+        let in_memory = builder.build_bytes(text).unwrap();
+
+        assert_eq!(vec![(0, 7)], in_memory.iter().collect::<Vec<_>>());
+        assert_eq!(streamed.iter().collect::<Vec<_>>(), in_memory.iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn custom_delimiter() {
+        let text = "a\n#\nb\n%\n";
+        let map = StrMapBuilder::new().delimiter(b'#').build(&mut text.as_bytes()).unwrap();
+
+        assert_eq!(2, map.len());
+        assert_eq!(b'#', map.delimiter());
+    }
+}
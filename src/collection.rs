@@ -0,0 +1,425 @@
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::Rng;
+use std::borrow::Cow;
+use std::ffi::OsStr;
+use std::fs;
+use std::path::Path;
+
+use crate::{CookieFile, LengthFilter, StrMapError};
+
+/// The suffixes of files that fortune(6) never takes for cookie files, indexes among them.
+static SKIPPED_SUFFIXES: &[&str] = &[
+    ".dat", ".pos", ".c", ".h", ".p", ".i", ".f", ".pas", ".ftn", ".ins.c", ".ins,pas", ".ins.ftn", ".sml",
+];
+
+/// A string picked from a collection, along with the file it came from.
+type Picked<'a> = (&'a CookieFile, Cow<'a, [u8]>);
+
+/// A set of cookie files from which strings are chosen the way fortune(6) chooses them.
+///
+/// By default, each file is weighted by the number of strings it holds, so every string in the
+/// collection is equally likely. In equal mode (`fortune -e`) each file is equally likely
+/// instead. Files may also be given an explicit percentage (`fortune 30% foo`); whatever is left
+/// over is shared among the remaining files according to the current mode.
+#[derive(Debug, Default)]
+pub struct Collection {
+    entries: Vec<Entry>,
+    groups: Vec<u32>,
+    equal: bool,
+}
+
+#[derive(Debug)]
+struct Entry {
+    file: CookieFile,
+    share: Share,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Share {
+    Residual,
+    Percent(u32),
+    Group(usize),
+}
+
+impl Collection {
+    /// Creates an empty collection.
+    pub fn new() -> Collection {
+        Collection::default()
+    }
+
+    /// Loads every cookie file in `dir`. See `read_dir`.
+    pub fn open_dir<P: AsRef<Path>>(dir: P) -> Result<Collection, StrMapError> {
+        let mut collection = Collection::new();
+        for file in Collection::read_dir(dir)? {
+            collection.add(file);
+        }
+        Ok(collection)
+    }
+
+    /// Opens every cookie file in `dir`, in order of file name, without adding them to a
+    /// collection. This is the scan `open_dir` does.
+    ///
+    /// Every regular file is taken for a cookie file except hidden files, indexes and the source
+    /// files fortune(6) skips. Files without a usable index are indexed in memory, as by
+    /// `CookieFile::open`.
+    pub fn read_dir<P: AsRef<Path>>(dir: P) -> Result<Vec<CookieFile>, StrMapError> {
+        let mut paths = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_file() && is_cookie_name(&path) {
+                paths.push(path);
+            }
+        }
+
+        paths.sort();
+        paths.into_iter().map(CookieFile::open).collect()
+    }
+
+    /// Adds a file to share in whatever probability is not claimed by explicit percentages.
+    pub fn add(&mut self, file: CookieFile) {
+        self.entries.push(Entry {
+            file,
+            share: Share::Residual,
+        });
+    }
+
+    /// Adds a file that should be chosen `percent` percent of the time.
+    pub fn add_with_percent(&mut self, file: CookieFile, percent: u32) {
+        self.entries.push(Entry {
+            file,
+            share: Share::Percent(percent),
+        });
+    }
+
+    /// Adds several files that together should be chosen `percent` percent of the time, like
+    /// `fortune 30% dir`. The percentage is shared among them the same way residual probability
+    /// is shared among other files.
+    pub fn add_group_with_percent<I: IntoIterator<Item = CookieFile>>(&mut self, files: I, percent: u32) {
+        let group = self.groups.len();
+        self.groups.push(percent);
+        for file in files {
+            self.entries.push(Entry {
+                file,
+                share: Share::Group(group),
+            });
+        }
+    }
+
+    /// Chooses between files with equal probability rather than by their number of strings,
+    /// like `fortune -e`.
+    pub fn set_equal(&mut self, equal: bool) {
+        self.equal = equal;
+    }
+
+    /// Whether equal mode is enabled.
+    pub fn is_equal(&self) -> bool {
+        self.equal
+    }
+
+    /// The number of files in the collection.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the collection contains no files.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns an iterator over the files in the collection.
+    pub fn files(&self) -> impl Iterator<Item = &CookieFile> {
+        self.entries.iter().map(|entry| &entry.file)
+    }
+
+    /// Returns each file along with the percentage of the time it will be chosen, like
+    /// `fortune -f`.
+    ///
+    /// Fails if the explicit percentages exceed 100, if they leave probability over with no file
+    /// to give it to or claim all of it while other files remain, or if a group given a
+    /// percentage holds no files.
+    pub fn probabilities(&self) -> Result<Vec<(&CookieFile, f64)>, StrMapError> {
+        let explicit: u32 = self.entries.iter()
+            .filter_map(|entry| match entry.share {
+                Share::Percent(percent) => Some(percent),
+                _ => None,
+            })
+            .chain(self.groups.iter().cloned())
+            .sum();
+        let residual_files = self.entries.iter().filter(|entry| entry.share == Share::Residual).count();
+
+        if explicit > 100 {
+            return Err(StrMapError::PercentagesExceeded { actual: explicit });
+        }
+        if explicit < 100 && residual_files == 0 && !self.entries.is_empty() {
+            return Err(StrMapError::ResidualUnclaimed { actual: 100 - explicit });
+        }
+        if explicit == 100 && residual_files != 0 {
+            return Err(StrMapError::NoResidualLeft);
+        }
+        for (group, &percent) in self.groups.iter().enumerate() {
+            if !self.entries.iter().any(|entry| entry.share == Share::Group(group)) {
+                return Err(StrMapError::EmptyGroup { actual: percent });
+            }
+        }
+
+        let residual = (100 - explicit) as f64;
+        Ok(self.entries.iter().map(|entry| {
+            let percent = match entry.share {
+                Share::Percent(percent) => percent as f64,
+                Share::Residual => self.share(residual, entry, |other| other == Share::Residual),
+                Share::Group(group) => {
+                    self.share(self.groups[group] as f64, entry, |other| other == Share::Group(group))
+                }
+            };
+            (&entry.file, percent)
+        }).collect())
+    }
+
+    /// Divides `percent` among the entries selected by `shares`, giving `entry` its part.
+    fn share<F: Fn(Share) -> bool>(&self, percent: f64, entry: &Entry, shares: F) -> f64 {
+        let (files, strings) = self.entries.iter()
+            .filter(|other| shares(other.share))
+            .fold((0, 0), |(files, strings), other| (files + 1, strings + other.file.len()));
+
+        if self.equal {
+            percent / files as f64
+        } else if strings == 0 {
+            0.0
+        } else {
+            percent * entry.file.len() as f64 / strings as f64
+        }
+    }
+
+    /// Picks a file according to the collection's weighting, then a string from that file.
+    ///
+    /// Returns `None` if there is nothing to choose from, and fails if the percentages are
+    /// inconsistent; see `probabilities`.
+    pub fn random<R: Rng + ?Sized>(
+        &self,
+        rng: &mut R,
+    ) -> Result<Option<Picked<'_>>, StrMapError> {
+        self.random_filtered(LengthFilter::Any, rng)
+    }
+
+    /// Picks a string that passes `filter`, like `fortune -s` or `fortune -l`.
+    ///
+    /// Files with no string that passes are left out of the running, and the rest keep their
+    /// relative weights.
+    pub fn random_filtered<R: Rng + ?Sized>(
+        &self,
+        filter: LengthFilter,
+        rng: &mut R,
+    ) -> Result<Option<Picked<'_>>, StrMapError> {
+        let probabilities = self.probabilities()?;
+        Ok(pick(&probabilities, filter, rng))
+    }
+}
+
+/// Picks a file with the given probabilities, then a string from it that passes `filter`.
+fn pick<'a, R: Rng + ?Sized>(
+    probabilities: &[(&'a CookieFile, f64)],
+    filter: LengthFilter,
+    rng: &mut R,
+) -> Option<Picked<'a>> {
+    // Files that can't produce a string don't get picked, however likely they are supposed to
+    // be. The header usually settles this without having to look at the text.
+    let candidates: Vec<_> = probabilities.iter().map(|&(file, percent)| {
+        let matches = match filter {
+            LengthFilter::Any => Vec::new(),
+            _ => file.map().filtered(file.text(), filter),
+        };
+
+        let possible = match filter {
+            LengthFilter::Any => !file.is_empty(),
+            _ => !matches.is_empty(),
+        };
+        (file, if possible { percent } else { 0.0 }, matches)
+    }).collect();
+
+    let weights = candidates.iter().map(|&(_, weight, _)| weight);
+    let (file, _, ref matches) = candidates[WeightedIndex::new(weights).ok()?.sample(rng)];
+
+    let idx = match filter {
+        LengthFilter::Any => file.map().random_index(rng)?,
+        _ => matches[rng.gen_range(0..matches.len())],
+    };
+    file.get(idx).map(|record| (file, record))
+}
+
+/// Whether the file at `path` could be a cookie file, going by its name alone.
+fn is_cookie_name(path: &Path) -> bool {
+    let name = match path.file_name().and_then(OsStr::to_str) {
+        Some(name) => name,
+        None => return false,
+    };
+    !name.starts_with('.') && !SKIPPED_SUFFIXES.iter().any(|suffix| name.ends_with(suffix))
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use std::path::Path;
+    use crate::{CookieFile, StrMapBuilder, StrMapError};
+    use super::Collection;
+
+    fn file(name: &str, count: usize) -> CookieFile {
+        let text: String = (0..count).map(|idx| format!("{} {}\n%\n", name, idx)).collect();
+        let map = StrMapBuilder::new().build(&mut text.as_bytes()).unwrap();
+        CookieFile::new(name, map, text.into_bytes())
+    }
+
+    fn percentages(collection: &Collection) -> Vec<f64> {
+        collection.probabilities().unwrap().into_iter().map(|(_, percent)| percent).collect()
+    }
+
+    #[test]
+    fn weights_by_string_count() {
+        let mut collection = Collection::new();
+        collection.add(file("a", 1));
+        collection.add(file("b", 3));
+
+        assert_eq!(vec![25.0, 75.0], percentages(&collection));
+    }
+
+    #[test]
+    fn equal_mode() {
+        let mut collection = Collection::new();
+        collection.add(file("a", 1));
+        collection.add(file("b", 3));
+        collection.set_equal(true);
+
+        assert_eq!(vec![50.0, 50.0], percentages(&collection));
+    }
+
+    #[test]
+    fn explicit_percentages() {
+        let mut collection = Collection::new();
+        collection.add_with_percent(file("a", 1), 30);
+        collection.add_with_percent(file("b", 3), 70);
+
+        assert_eq!(vec![30.0, 70.0], percentages(&collection));
+    }
+
+    #[test]
+    fn residual_is_shared_by_count() {
+        let mut collection = Collection::new();
+        collection.add_with_percent(file("a", 1), 40);
+        collection.add(file("b", 1));
+        collection.add(file("c", 2));
+
+        assert_eq!(vec![40.0, 20.0, 40.0], percentages(&collection));
+    }
+
+    #[test]
+    fn groups_share_their_percentage() {
+        let mut collection = Collection::new();
+        collection.add_group_with_percent(vec![file("a", 1), file("b", 3)], 40);
+        collection.add(file("c", 1));
+
+        assert_eq!(vec![10.0, 30.0, 60.0], percentages(&collection));
+
+        collection.set_equal(true);
+        assert_eq!(vec![20.0, 20.0, 60.0], percentages(&collection));
+    }
+
+    #[test]
+    fn inconsistent_percentages_are_errors() {
+        let mut over = Collection::new();
+        over.add_with_percent(file("a", 1), 60);
+        over.add_with_percent(file("b", 1), 60);
+        assert!(over.probabilities().is_err());
+
+        let mut under = Collection::new();
+        under.add_with_percent(file("a", 1), 60);
+        assert!(under.probabilities().is_err());
+
+        let mut full = Collection::new();
+        full.add_with_percent(file("a", 1), 100);
+        full.add(file("b", 1));
+        assert!(full.probabilities().is_err());
+
+        let mut groups = Collection::new();
+        groups.add_group_with_percent(vec![file("a", 1)], 70);
+        groups.add_with_percent(file("b", 1), 40);
+        assert!(groups.probabilities().is_err());
+    }
+
+    #[test]
+    fn random_reports_inconsistent_percentages() {
+        let mut collection = Collection::new();
+        collection.add_with_percent(file("a", 1), 60);
+
+        match collection.random(&mut StdRng::seed_from_u64(1)) {
+            Err(StrMapError::ResidualUnclaimed { actual: 40 }) => (),
+            other => panic!("unexpected result: {:?}", other.map(|picked| picked.map(|(_, record)| record))),
+        }
+        assert!(Collection::new().random(&mut StdRng::seed_from_u64(1)).unwrap().is_none());
+    }
+
+    #[test]
+    fn empty_groups_are_errors() {
+        let mut collection = Collection::new();
+        collection.add_group_with_percent(Vec::new(), 30);
+        collection.add(file("a", 1));
+
+        match collection.probabilities() {
+            Err(StrMapError::EmptyGroup { actual: 30 }) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn random_respects_weights() {
+        let mut collection = Collection::new();
+        collection.add_with_percent(file("a", 1), 100);
+        collection.add_with_percent(file("b", 5), 0);
+
+        let mut rng = StdRng::seed_from_u64(3);
+        for _ in 0..50 {
+            let (file, record) = collection.random(&mut rng).unwrap().unwrap();
+            assert_eq!(Path::new("a"), file.path());
+            assert_eq!(&b"a 0\n"[..], &*record);
+        }
+    }
+
+    #[test]
+    fn random_skips_empty_files() {
+        let mut collection = Collection::new();
+        collection.add(file("empty", 0));
+        collection.add(file("b", 2));
+        collection.set_equal(true);
+
+        let mut rng = StdRng::seed_from_u64(9);
+        for _ in 0..20 {
+            assert_eq!(Path::new("b"), collection.random(&mut rng).unwrap().unwrap().0.path());
+        }
+    }
+
+    #[test]
+    fn random_filtered_drops_files_without_matches() {
+        use crate::LengthFilter;
+
+        let mut collection = Collection::new();
+        collection.add(file("short", 5));
+        collection.add(file("a much longer name", 1));
+
+        let mut rng = StdRng::seed_from_u64(4);
+        for _ in 0..20 {
+            let (file, record) = collection.random_filtered(LengthFilter::Long(10), &mut rng).unwrap().unwrap();
+            assert_eq!(Path::new("a much longer name"), file.path());
+            assert_eq!(&b"a much longer name 0\n"[..], &*record);
+        }
+        assert!(collection.random_filtered(LengthFilter::Short(3), &mut rng).unwrap().is_none());
+    }
+
+    #[test]
+    fn open_dir_finds_cookie_files() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures").join("collection");
+        let collection = Collection::open_dir(dir).unwrap();
+        let names: Vec<_> = collection.files().map(|file| file.path().file_name().unwrap().to_owned()).collect();
+
+        assert_eq!(vec!["one", "three", "two"], names);
+        assert_eq!(vec![1, 3, 2], collection.files().map(|file| file.len()).collect::<Vec<_>>());
+    }
+}
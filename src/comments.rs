@@ -0,0 +1,42 @@
+use alloc::borrow::Cow;
+use alloc::vec::Vec;
+
+/// Removes comment lines from a string taken from a file flagged as containing comments, like
+/// `strfile -C`. A comment is any line beginning with two delimiter bytes, such as `%%`.
+///
+/// The string is borrowed unchanged if it contains no comments.
+pub fn strip_comments(record: &[u8], delimiter: u8) -> Cow<'_, [u8]> {
+    let is_comment = |line: &[u8]| line.starts_with(&[delimiter, delimiter]);
+    if !record.split(|&b| b == b'\n').any(is_comment) {
+        return Cow::Borrowed(record);
+    }
+
+    let mut output = Vec::with_capacity(record.len());
+    let mut rest = record;
+    while !rest.is_empty() {
+        let next = rest.iter().position(|&b| b == b'\n').map_or(rest.len(), |n| n + 1);
+        if !is_comment(&rest[..next]) {
+            output.extend_from_slice(&rest[..next]);
+        }
+        rest = &rest[next..];
+    }
+    Cow::Owned(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+    use super::strip_comments;
+
+    #[test]
+    fn removes_comment_lines() {
+        assert_eq!(&b"a\nb\n"[..], &*strip_comments(b"%% from somewhere\na\n%%\nb\n", b'%'));
+        assert_eq!(&b"a\n"[..], &*strip_comments(b"a\n%% trailing", b'%'));
+    }
+
+    #[test]
+    fn single_delimiters_are_not_comments() {
+        let record = b"%d items\n 50%% off\n";
+        assert!(matches!(strip_comments(record, b'%'), Cow::Borrowed(_)));
+    }
+}
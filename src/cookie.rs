@@ -0,0 +1,425 @@
+use rand::Rng;
+use std::borrow::Cow;
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+use std::str;
+
+use crate::rot13::{rot13, rot13_in_place, rot13_text};
+use crate::{strip_comments, LengthFilter, StrFlags, StrMap, StrMapBuilder, StrMapError};
+
+/// A cookie file paired with its strfile index.
+///
+/// Strings are returned with their delimiter line already removed, so `get(0)` on a file
+/// beginning with `"Hello\n%\n"` yields `"Hello\n"`. If the index is flagged as containing
+/// comments, comment lines are removed, and if it is flagged as rotated, strings are decoded from
+/// rot13 as well; use `get_raw` to see them as they appear on disk.
+#[derive(Debug)]
+pub struct CookieFile {
+    path: PathBuf,
+    map: StrMap,
+    text: Vec<u8>,
+}
+
+impl CookieFile {
+    /// Opens the cookie file at `path` along with the index next to it, named by appending
+    /// `.dat` to the file name.
+    ///
+    /// If there is no index, it can't be parsed, or it is stale (older than the text, or ending
+    /// somewhere other than the end of the text), an index is built in memory instead. A stale
+    /// index still supplies the delimiter and flags, and a randomized one is shuffled afresh.
+    /// Nothing is written to disk either way.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<CookieFile, StrMapError> {
+        let path = path.as_ref();
+        let text = fs::read(path)?;
+
+        let dat = dat_path(path);
+        let index = match File::open(&dat) {
+            Ok(file) => match StrMap::read(&mut BufReader::new(file)) {
+                Ok(map) => Some(map),
+                Err(StrMapError::Io(e)) => return Err(e.into()),
+                Err(_) => None,
+            },
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e.into()),
+        };
+
+        let map = match index {
+            Some(map) if !is_stale(&map, &text, path, &dat) => map,
+            Some(map) => rebuild(&map, &text)?,
+            None => StrMapBuilder::new().build_bytes(&text)?,
+        };
+
+        Ok(CookieFile::new(path, map, text))
+    }
+
+    /// Pairs an index with the text it describes. `path` is used only for reporting.
+    pub fn new<P: Into<PathBuf>>(path: P, map: StrMap, text: Vec<u8>) -> CookieFile {
+        CookieFile {
+            path: path.into(),
+            map,
+            text,
+        }
+    }
+
+    /// The path of the cookie file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The index describing this file.
+    pub fn map(&self) -> &StrMap {
+        &self.map
+    }
+
+    /// The full text of the cookie file.
+    pub fn text(&self) -> &[u8] {
+        &self.text
+    }
+
+    /// The number of strings in the file.
+    pub fn len(&self) -> usize {
+        self.map.len() as usize
+    }
+
+    /// Whether the file contains no strings.
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Returns the string at `idx`, if there is one, without comments and decoded from rot13 if
+    /// need be.
+    pub fn get(&self, idx: usize) -> Option<Cow<'_, [u8]>> {
+        let record = self.get_raw(idx)?;
+        let record = if self.map.has_comments() {
+            strip_comments(record, self.map.delimiter())
+        } else {
+            Cow::Borrowed(record)
+        };
+
+        if !self.map.is_rotated() {
+            return Some(record);
+        }
+        Some(Cow::Owned(match record {
+            Cow::Borrowed(record) => rot13(record),
+            Cow::Owned(mut record) => {
+                rot13_in_place(&mut record);
+                record
+            }
+        }))
+    }
+
+    /// Picks a string uniformly at random, decoded from rot13 if need be.
+    pub fn random<R: Rng + ?Sized>(&self, rng: &mut R) -> Option<Cow<'_, [u8]>> {
+        self.map.random_index(rng).and_then(|idx| self.get(idx))
+    }
+
+    /// Picks a string that passes `filter` uniformly at random, decoded from rot13 if need be.
+    pub fn random_filtered<R: Rng + ?Sized>(&self, filter: LengthFilter, rng: &mut R) -> Option<Cow<'_, [u8]>> {
+        self.map.random_filtered(&self.text, filter, rng).and_then(|idx| self.get(idx))
+    }
+
+    /// Returns the string at `idx` exactly as it appears in the file, without decoding it.
+    pub fn get_raw(&self, idx: usize) -> Option<&[u8]> {
+        self.map.record(&self.text, idx)
+    }
+
+    /// Returns the string at `idx`, if there is one and it is valid UTF-8.
+    pub fn get_str(&self, idx: usize) -> Option<Cow<'_, str>> {
+        match self.get(idx)? {
+            Cow::Borrowed(record) => str::from_utf8(record).ok().map(Cow::Borrowed),
+            Cow::Owned(record) => String::from_utf8(record).ok().map(Cow::Owned),
+        }
+    }
+
+    /// Converts a plain cookie file into a rotated one, encoding its text with rot13 and setting
+    /// the rotated flag on its index. Files that are already rotated are returned unchanged.
+    ///
+    /// Writing out `map()` and `text()` afterward gives the same result as rotating the text by
+    /// hand and running `strfile -x` over it.
+    pub fn rotate(mut self) -> CookieFile {
+        if !self.map.is_rotated() {
+            self.text = rot13_text(&self.text, self.map.delimiter());
+            self.map.flags.insert(StrFlags::STR_ROTATED);
+        }
+        self
+    }
+
+    /// Returns an iterator over the strings in index order.
+    pub fn iter(&self) -> CookieFileIter<'_> {
+        CookieFileIter {
+            file: self,
+            idx: 0,
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a CookieFile {
+    type Item = Cow<'a, [u8]>;
+    type IntoIter = CookieFileIter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+pub struct CookieFileIter<'a> {
+    file: &'a CookieFile,
+    idx: usize,
+}
+
+impl<'a> Iterator for CookieFileIter<'a> {
+    type Item = Cow<'a, [u8]>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let record = self.file.get(self.idx)?;
+        self.idx += 1;
+        Some(record)
+    }
+}
+
+/// Whether the index at `dat` no longer describes `text`, the contents of the file at `path`.
+fn is_stale(map: &StrMap, text: &[u8], path: &Path, dat: &Path) -> bool {
+    if !map.is_empty() && map.end() != text.len() as u64 {
+        return true;
+    }
+
+    let modified = |path: &Path| fs::metadata(path).and_then(|metadata| metadata.modified()).ok();
+    match (modified(dat), modified(path)) {
+        (Some(dat), Some(text)) => dat < text,
+        _ => false,
+    }
+}
+
+/// Builds a fresh index for `text` with the delimiter and flags of `stale`.
+fn rebuild(stale: &StrMap, text: &[u8]) -> io::Result<StrMap> {
+    let mut map = StrMapBuilder::new()
+        .delimiter(stale.delimiter())
+        .rotated(stale.is_rotated())
+        .comments(stale.has_comments())
+        .ordered(stale.is_ordered())
+        .build_bytes(text)?;
+
+    if stale.is_random() {
+        map.shuffle(&mut rand::thread_rng());
+    }
+    Ok(map)
+}
+
+/// Returns the conventional path of the index for the cookie file at `path`.
+pub fn dat_path(path: &Path) -> PathBuf {
+    let mut dat = OsString::from(path.as_os_str());
+    dat.push(".dat");
+    PathBuf::from(dat)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::io::Cursor;
+    use std::path::{Path, PathBuf};
+    use std::process;
+    use crate::{StrMap, StrMapBuilder};
+    use super::{dat_path, CookieFile};
+
+    static SAMPLE: &str = include_str!("../sample.txt");
+    static SAMPLE_DAT: &[u8] = include_bytes!("../sample.txt.dat");
+
+    fn sample() -> CookieFile {
+        let map = StrMap::read(&mut Cursor::new(SAMPLE_DAT)).unwrap();
+        CookieFile::new("sample.txt", map, SAMPLE.as_bytes().to_vec())
+    }
+
+    #[test]
+    fn strings_exclude_delimiter() {
+        let file = sample();
+
+        assert_eq!(4, file.len());
+        assert_eq!("This file exists\n", file.get_str(0).unwrap());
+        assert_eq!("A known sample file\n", file.get_str(2).unwrap());
+        assert_eq!(None, file.get(4));
+    }
+
+    #[test]
+    fn final_string_without_delimiter() {
+        let file = sample();
+        assert_eq!("To use with strfile\n\n", file.get_str(3).unwrap());
+    }
+
+    #[test]
+    fn iterates_in_index_order() {
+        let file = sample();
+        let strings: Vec<_> = file.iter().collect();
+
+        assert_eq!(4, strings.len());
+        assert_eq!(&b"Solely to provide\n"[..], &*strings[1]);
+    }
+
+    #[test]
+    fn folded_empty_strings_are_skipped() {
+        let text = b"a\n%\n%\nbc\n%\n".to_vec();
+        let map = StrMapBuilder::new().build(&mut &text[..]).unwrap();
+        let file = CookieFile::new("folded", map, text);
+
+        assert_eq!("bc\n", file.get_str(1).unwrap());
+    }
+
+    #[test]
+    fn comments_are_removed() {
+        let text = b"%% sample.txt\na\n%\nb\n%% b is for 50%\nc\n%\n".to_vec();
+        let map = StrMapBuilder::new().comments(true).build(&mut &text[..]).unwrap();
+        let file = CookieFile::new("commented", map, text.clone()).rotate();
+
+        assert!(file.map().has_comments());
+        assert_eq!("a\n", file.get_str(0).unwrap());
+        assert_eq!("b\nc\n", file.get_str(1).unwrap());
+        assert_eq!(&b"%% fnzcyr.gkg\nn\n"[..], file.get_raw(0).unwrap());
+
+        let map = StrMapBuilder::new().build(&mut &text[..]).unwrap();
+        let file = CookieFile::new("uncommented", map, text);
+        assert_eq!("%% sample.txt\na\n", file.get_str(0).unwrap());
+    }
+
+    #[test]
+    fn opens_text_and_dat() {
+        let file = CookieFile::open(Path::new(env!("CARGO_MANIFEST_DIR")).join("sample.txt")).unwrap();
+        assert_eq!("Solely to provide\n", file.get_str(1).unwrap());
+    }
+
+    /// A scratch directory for tests that need real files, removed again on drop.
+    struct Scratch(PathBuf);
+
+    impl Scratch {
+        fn new(name: &str) -> Scratch {
+            let dir = env::temp_dir().join(format!("strmap-{}-{}", name, process::id()));
+            fs::create_dir_all(&dir).unwrap();
+            Scratch(dir)
+        }
+    }
+
+    impl Drop for Scratch {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn missing_dat_is_built_in_memory() {
+        let scratch = Scratch::new("missing-dat");
+        let path = scratch.0.join("adhoc");
+        fs::write(&path, "a\n%\nbc\n%\n").unwrap();
+
+        let file = CookieFile::open(&path).unwrap();
+        assert_eq!(2, file.len());
+        assert_eq!("bc\n", file.get_str(1).unwrap());
+        assert!(!dat_path(&path).exists());
+    }
+
+    #[test]
+    fn stale_dat_is_rebuilt_in_memory() {
+        let scratch = Scratch::new("stale-dat");
+        let path = scratch.0.join("edited");
+        fs::write(&path, "a\n#\nbc\n#\ndef\n").unwrap();
+
+        let old = StrMapBuilder::new().delimiter(b'#').rotated(true).build_bytes(b"a\n#\nbc\n").unwrap();
+        let mut dat = Vec::new();
+        old.write(&mut dat).unwrap();
+        fs::write(dat_path(&path), dat).unwrap();
+
+        let file = CookieFile::open(&path).unwrap();
+        assert_eq!(3, file.len());
+        assert!(file.map().is_rotated());
+        assert_eq!(&b"def\n"[..], file.get_raw(2).unwrap());
+    }
+
+    #[test]
+    fn stale_random_dat_is_reshuffled() {
+        let scratch = Scratch::new("stale-random-dat");
+        let path = scratch.0.join("shuffled");
+        fs::write(&path, "a\n%\nbc\n%\ndef\n").unwrap();
+
+        let mut old = StrMapBuilder::new().build_bytes(b"a\n%\nbc\n").unwrap();
+        old.shuffle(&mut rand::thread_rng());
+        let mut dat = Vec::new();
+        old.write(&mut dat).unwrap();
+        fs::write(dat_path(&path), dat).unwrap();
+
+        let file = CookieFile::open(&path).unwrap();
+        assert!(file.map().is_random());
+        assert_eq!(3, file.len());
+
+        let mut strings: Vec<_> = (0..file.len()).map(|idx| file.get_str(idx).unwrap().into_owned()).collect();
+        strings.sort();
+        assert_eq!(vec!["a\n", "bc\n", "def\n"], strings);
+    }
+
+    #[test]
+    fn corrupt_dat_is_rebuilt_in_memory() {
+        let scratch = Scratch::new("corrupt-dat");
+        let path = scratch.0.join("corrupt");
+        fs::write(&path, "a\n%\nbc\n%\n").unwrap();
+        fs::write(dat_path(&path), b"not an index").unwrap();
+
+        let file = CookieFile::open(&path).unwrap();
+        assert_eq!(2, file.len());
+        assert_eq!("bc\n", file.get_str(1).unwrap());
+    }
+
+    #[test]
+    fn rotated_files_are_decoded() {
+        let file = sample().rotate();
+
+        assert!(file.map().is_rotated());
+        assert_eq!(&b"Fbyryl gb cebivqr\n"[..], file.get_raw(1).unwrap());
+        assert_eq!("Solely to provide\n", file.get_str(1).unwrap());
+    }
+
+    #[test]
+    fn rotating_twice_is_a_no_op() {
+        let once = sample().rotate();
+        let text = once.text().to_vec();
+
+        assert_eq!(text, once.rotate().text());
+    }
+
+    #[test]
+    fn rotated_dat_sets_flag() {
+        let file = sample().rotate();
+        let mut dat = Vec::new();
+        file.map().write(&mut dat).unwrap();
+
+        assert_eq!(4, dat[19]);
+        assert!(StrMap::read(&mut Cursor::new(dat)).unwrap().is_rotated());
+    }
+
+    #[test]
+    fn random_string_comes_from_file() {
+        use rand::rngs::StdRng;
+        use rand::SeedableRng;
+
+        let file = sample();
+        let mut rng = StdRng::seed_from_u64(42);
+        for _ in 0..20 {
+            let record = file.random(&mut rng).unwrap();
+            assert!(file.iter().any(|candidate| candidate == record));
+        }
+    }
+
+    #[test]
+    fn random_filtered_string() {
+        use rand::rngs::StdRng;
+        use rand::SeedableRng;
+        use crate::LengthFilter;
+
+        let file = sample();
+        let record = file.random_filtered(LengthFilter::Short(17), &mut StdRng::seed_from_u64(0)).unwrap();
+        assert_eq!(&b"This file exists\n"[..], &*record);
+    }
+
+    #[test]
+    fn dat_path_appends_extension() {
+        assert_eq!(Path::new("fortunes/zippy.dat"), dat_path(Path::new("fortunes/zippy")));
+    }
+}
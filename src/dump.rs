@@ -0,0 +1,215 @@
+use std::io;
+
+use crate::rot13::rot13;
+use crate::{strip_comments, StrFlags, StrMap, FLAG_NAMES};
+
+/// The output format of `StrMap::dump`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DumpFormat {
+    /// Aligned text meant for people.
+    Text,
+
+    /// A single JSON object meant for other programs.
+    Json,
+}
+
+impl StrMap {
+    /// Writes a description of this index to `out`: its layout and byte order, every header field,
+    /// the decoded flags, and the offset table in index order followed by the final offset.
+    ///
+    /// If `text` is given, each offset is annotated with the first line of its string, decoded
+    /// from rot13 if the index is flagged as rotated.
+    pub fn dump<W: io::Write>(&self, out: &mut W, format: DumpFormat, text: Option<&[u8]>) -> io::Result<()> {
+        let first_lines: Option<Vec<_>> = text.map(|text| {
+            (0..self.offsets.len()).map(|idx| self.first_line(text, idx)).collect()
+        });
+
+        match format {
+            DumpFormat::Text => self.dump_text(out, first_lines.as_deref()),
+            DumpFormat::Json => self.dump_json(out, first_lines.as_deref()),
+        }
+    }
+
+    fn dump_text<W: io::Write>(&self, out: &mut W, first_lines: Option<&[Option<String>]>) -> io::Result<()> {
+        let bits = if self.layout.is_64bit() { 64 } else { 32 };
+        writeln!(out, "layout:    {} ({}-bit)", self.layout, bits)?;
+        writeln!(out, "endian:    {}", self.endianness)?;
+        writeln!(out, "version:   {}", self.version)?;
+        writeln!(out, "count:     {}", self.count)?;
+        writeln!(out, "longest:   {}", self.longest)?;
+        writeln!(out, "shortest:  {}", self.shortest)?;
+
+        let names = flag_names(self.flags);
+        let names = if names.is_empty() { "none".to_owned() } else { names.join(" | ") };
+        writeln!(out, "flags:     {:#x} ({})", self.flags.bits(), names)?;
+        writeln!(out, "delimiter: {} ({:#04x})", printable(self.delimiter), self.delimiter)?;
+
+        writeln!(out, "offsets:")?;
+        for (idx, &(start, _)) in self.offsets.iter().enumerate() {
+            write!(out, "{:>8} {:>10}", idx, start)?;
+            match first_lines.and_then(|lines| lines[idx].as_ref()) {
+                Some(line) => writeln!(out, "  {}", line)?,
+                None => writeln!(out)?,
+            }
+        }
+        writeln!(out, "{:>8} {:>10}", "end", self.end())
+    }
+
+    fn dump_json<W: io::Write>(&self, out: &mut W, first_lines: Option<&[Option<String>]>) -> io::Result<()> {
+        writeln!(out, "{{")?;
+        writeln!(out, "  \"layout\": \"{}\",", self.layout)?;
+        writeln!(out, "  \"endianness\": \"{}\",", self.endianness)?;
+        writeln!(out, "  \"version\": {},", self.version)?;
+        writeln!(out, "  \"count\": {},", self.count)?;
+        writeln!(out, "  \"longest\": {},", self.longest)?;
+        writeln!(out, "  \"shortest\": {},", self.shortest)?;
+
+        let names: Vec<_> = flag_names(self.flags).iter().map(|name| json_string(name)).collect();
+        writeln!(out, "  \"flags\": {{ \"bits\": {}, \"names\": [{}] }},", self.flags.bits(), names.join(", "))?;
+        writeln!(out, "  \"delimiter\": {},", json_string(&(self.delimiter as char).to_string()))?;
+
+        write!(out, "  \"offsets\": [")?;
+        for (idx, &(start, _)) in self.offsets.iter().enumerate() {
+            let separator = if idx == 0 { "" } else { "," };
+            write!(out, "{}\n    {{ \"index\": {}, \"offset\": {}", separator, idx, start)?;
+            if let Some(lines) = first_lines {
+                match lines[idx] {
+                    Some(ref line) => write!(out, ", \"first_line\": {}", json_string(line))?,
+                    None => write!(out, ", \"first_line\": null")?,
+                }
+            }
+            write!(out, " }}")?;
+        }
+        if !self.offsets.is_empty() {
+            write!(out, "\n  ")?;
+        }
+        writeln!(out, "],")?;
+
+        writeln!(out, "  \"end\": {}", self.end())?;
+        writeln!(out, "}}")
+    }
+
+    /// The first line of the string at `idx` other than a comment, without its newline, or `None`
+    /// if the string lies outside `text`.
+    fn first_line(&self, text: &[u8], idx: usize) -> Option<String> {
+        let record = self.record(text, idx)?;
+        let record = if self.has_comments() { strip_comments(record, self.delimiter) } else { record.into() };
+        let record = &*record;
+        let line = record.split(|&b| b == b'\n').next().unwrap_or(record);
+        let line = if self.is_rotated() { rot13(line) } else { line.to_vec() };
+        Some(String::from_utf8_lossy(&line).into_owned())
+    }
+}
+
+/// The names of the flags set in `flags`, as strfile's header spells them.
+fn flag_names(flags: StrFlags) -> Vec<&'static str> {
+    FLAG_NAMES.iter().filter(|&&(flag, _)| flags.contains(flag)).map(|&(_, name)| name).collect()
+}
+
+/// Shows a delimiter byte as itself if it is printable, and as an escape otherwise.
+fn printable(b: u8) -> String {
+    if b.is_ascii_graphic() {
+        format!("'{}'", b as char)
+    } else {
+        format!("'{}'", b.escape_ascii())
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use crate::{StrMap, StrMapBuilder};
+    use super::{json_string, DumpFormat};
+
+    static SAMPLE: &[u8] = include_bytes!("../sample.txt");
+    static SAMPLE_DAT: &[u8] = include_bytes!("../sample.txt.dat");
+    static SAMPLE_DAT_64: &[u8] = include_bytes!("../sample.txt-64.dat");
+
+    fn dump(dat: &[u8], format: DumpFormat, text: Option<&[u8]>) -> String {
+        let map = StrMap::read(&mut Cursor::new(dat)).unwrap();
+        let mut out = Vec::new();
+        map.dump(&mut out, format, text).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn text_dump() {
+        assert_eq!(
+            "layout:    x86 (32-bit)\n\
+             endian:    big\n\
+             version:   2\n\
+             count:     4\n\
+             longest:   21\n\
+             shortest:  17\n\
+             flags:     0x0 (none)\n\
+             delimiter: '%' (0x25)\n\
+             offsets:\n       \
+                    0          0\n       \
+                    1         19\n       \
+                    2         39\n       \
+                    3         61\n     \
+                  end         82\n",
+            dump(SAMPLE_DAT, DumpFormat::Text, None)
+        );
+    }
+
+    #[test]
+    fn text_dump_reports_64bit_layout() {
+        let output = dump(SAMPLE_DAT_64, DumpFormat::Text, None);
+        assert!(output.starts_with("layout:    x64 (64-bit)\nendian:    big\nversion:   1\n"));
+    }
+
+    #[test]
+    fn annotates_first_lines() {
+        let output = dump(SAMPLE_DAT, DumpFormat::Text, Some(SAMPLE));
+        assert!(output.contains("       2         39  A known sample file\n"));
+    }
+
+    #[test]
+    fn json_dump() {
+        let map = StrMapBuilder::new().ordered(true).rotated(true).build(&mut &b"Nopq\n%\nNnn\n"[..]).unwrap();
+        let mut out = Vec::new();
+        map.dump(&mut out, DumpFormat::Json, Some(b"Nopq\n%\nNnn\n")).unwrap();
+
+        assert_eq!(
+            "{\n  \"layout\": \"x86\",\n  \"endianness\": \"big\",\n  \"version\": 2,\n  \"count\": 2,\n  \"longest\": 5,\n  \
+             \"shortest\": 4,\n  \"flags\": { \"bits\": 6, \"names\": [\"STR_ORDERED\", \"STR_ROTATED\"] },\n  \
+             \"delimiter\": \"%\",\n  \"offsets\": [\n    \
+             { \"index\": 0, \"offset\": 7, \"first_line\": \"Aaa\" },\n    \
+             { \"index\": 1, \"offset\": 0, \"first_line\": \"Abcd\" }\n  ],\n  \"end\": 11\n}\n",
+            String::from_utf8(out).unwrap()
+        );
+    }
+
+    #[test]
+    fn json_dump_of_empty_index() {
+        let map = StrMapBuilder::new().build(&mut &b""[..]).unwrap();
+        let mut out = Vec::new();
+        map.dump(&mut out, DumpFormat::Json, None).unwrap();
+
+        assert!(String::from_utf8(out).unwrap().contains("\"offsets\": [],\n  \"end\": 0\n"));
+    }
+
+    #[test]
+    fn json_strings_are_escaped() {
+        assert_eq!(r#""a\"b\\c\n\u0001""#, json_string("a\"b\\c\n\u{1}"));
+    }
+}
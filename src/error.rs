@@ -0,0 +1,171 @@
+#[cfg(feature = "std")]
+use std::error;
+use core::fmt;
+#[cfg(feature = "std")]
+use std::io;
+
+/// An error encountered while reading a strfile index or weighing a collection of cookie files.
+///
+/// Every variant describing malformed input carries the byte position in the index at which the
+/// problem was found, along with the value that was expected there and the value actually found.
+#[derive(Debug)]
+pub enum StrMapError {
+    /// The underlying reader failed.
+    #[cfg(feature = "std")]
+    Io(io::Error),
+
+    /// The index ended before its header was complete. `expected` is the length of the header in
+    /// bytes and `actual` is the length of the index.
+    TruncatedHeader { position: u64, expected: u64, actual: u64 },
+
+    /// The header sets flag bits this crate does not know about. `expected` is the mask of known
+    /// flags and `actual` is the raw flags field.
+    UnknownFlags { position: u64, expected: u32, actual: u32 },
+
+    /// The header names a strfile version newer than this crate understands. `expected` is the
+    /// latest supported version.
+    UnsupportedVersion { position: u64, expected: u32, actual: u32 },
+
+    /// The number of offsets in the index does not agree with the string count in the header.
+    CountMismatch { position: u64, expected: u32, actual: u32 },
+
+    /// An offset is smaller than the one preceding it in an index that is neither ordered nor
+    /// randomized. `expected` is the preceding offset.
+    OffsetOutOfOrder { position: u64, expected: u64, actual: u64 },
+
+    /// An offset lies beyond the end of the text as recorded by the final offset. `expected` is the
+    /// final offset.
+    OffsetOutOfBounds { position: u64, expected: u64, actual: u64 },
+
+    /// The explicit percentages given to a `Collection` add up to more than 100. `actual` is
+    /// their sum.
+    #[cfg(feature = "std")]
+    PercentagesExceeded { actual: u32 },
+
+    /// The explicit percentages given to a `Collection` leave probability over, but no file is
+    /// left to share it. `actual` is the unclaimed percentage.
+    #[cfg(feature = "std")]
+    ResidualUnclaimed { actual: u32 },
+
+    /// The explicit percentages given to a `Collection` claim all of the probability, leaving
+    /// none for the files without one.
+    #[cfg(feature = "std")]
+    NoResidualLeft,
+
+    /// A group of files given an explicit percentage holds no files to share it. `actual` is the
+    /// group's percentage.
+    #[cfg(feature = "std")]
+    EmptyGroup { actual: u32 },
+}
+
+impl fmt::Display for StrMapError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            #[cfg(feature = "std")]
+            StrMapError::Io(ref e) => write!(f, "{}", e),
+            StrMapError::TruncatedHeader { position, expected, actual } => write!(
+                f,
+                "Header truncated at byte {}: expected {} bytes, found {}",
+                position, expected, actual
+            ),
+            StrMapError::UnknownFlags { position, expected, actual } => write!(
+                f,
+                "Unknown flags at byte {}: expected bits within {:#x}, found {:#x}",
+                position, expected, actual
+            ),
+            StrMapError::UnsupportedVersion { position, expected, actual } => write!(
+                f,
+                "Unsupported version at byte {}: expected at most {}, found {}",
+                position, expected, actual
+            ),
+            StrMapError::CountMismatch { position, expected, actual } => write!(
+                f,
+                "Str count in header does not match str count in data at byte {}: Header({}) vs Data({})",
+                position, expected, actual
+            ),
+            StrMapError::OffsetOutOfOrder { position, expected, actual } => write!(
+                f,
+                "Offset out of order at byte {}: expected at least {}, found {}",
+                position, expected, actual
+            ),
+            StrMapError::OffsetOutOfBounds { position, expected, actual } => write!(
+                f,
+                "Offset out of bounds at byte {}: expected at most {}, found {}",
+                position, expected, actual
+            ),
+            #[cfg(feature = "std")]
+            StrMapError::PercentagesExceeded { actual } => write!(f, "Probabilities sum to {}%", actual),
+            #[cfg(feature = "std")]
+            StrMapError::ResidualUnclaimed { actual } => {
+                write!(f, "No place to put residual probability ({}%)", actual)
+            }
+            #[cfg(feature = "std")]
+            StrMapError::NoResidualLeft => write!(f, "No probability left to put in residual files"),
+            #[cfg(feature = "std")]
+            StrMapError::EmptyGroup { actual } => write!(f, "No files to share {}% of the probability", actual),
+        }
+    }
+}
+
+impl StrMapError {
+    /// Moves every position reported by this error `base` bytes further into the index, for
+    /// errors found in data that was read starting at `base`.
+    #[cfg(feature = "std")]
+    pub(crate) fn offset_by(self, base: u64) -> StrMapError {
+        match self {
+            StrMapError::Io(e) => StrMapError::Io(e),
+            StrMapError::TruncatedHeader { position, expected, actual } => StrMapError::TruncatedHeader {
+                position: position + base,
+                expected,
+                actual: actual + base,
+            },
+            StrMapError::UnknownFlags { position, expected, actual } => {
+                StrMapError::UnknownFlags { position: position + base, expected, actual }
+            }
+            StrMapError::UnsupportedVersion { position, expected, actual } => {
+                StrMapError::UnsupportedVersion { position: position + base, expected, actual }
+            }
+            StrMapError::CountMismatch { position, expected, actual } => {
+                StrMapError::CountMismatch { position: position + base, expected, actual }
+            }
+            StrMapError::OffsetOutOfOrder { position, expected, actual } => {
+                StrMapError::OffsetOutOfOrder { position: position + base, expected, actual }
+            }
+            StrMapError::OffsetOutOfBounds { position, expected, actual } => {
+                StrMapError::OffsetOutOfBounds { position: position + base, expected, actual }
+            }
+            e => e,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl error::Error for StrMapError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            StrMapError::Io(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<io::Error> for StrMapError {
+    fn from(e: io::Error) -> StrMapError {
+        StrMapError::Io(e)
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<StrMapError> for io::Error {
+    fn from(e: StrMapError) -> io::Error {
+        match e {
+            StrMapError::Io(e) => e,
+            e @ StrMapError::PercentagesExceeded { .. }
+            | e @ StrMapError::ResidualUnclaimed { .. }
+            | e @ StrMapError::NoResidualLeft
+            | e @ StrMapError::EmptyGroup { .. } => io::Error::new(io::ErrorKind::InvalidInput, e),
+            e => io::Error::new(io::ErrorKind::InvalidData, e),
+        }
+    }
+}
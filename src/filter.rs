@@ -0,0 +1,119 @@
+use alloc::vec::Vec;
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use crate::StrMap;
+
+/// Restricts strings by length, like fortune's `-s`, `-l` and `-n` options.
+///
+/// Lengths are measured on a string's content, not counting its delimiter line, which is the same
+/// way strfile measures the `longest` and `shortest` header fields.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum LengthFilter {
+    /// Any length will do.
+    #[default]
+    Any,
+
+    /// Only strings at most this many bytes long, like `fortune -s -n N`.
+    Short(u32),
+
+    /// Only strings more than this many bytes long, like `fortune -l -n N`.
+    Long(u32),
+}
+
+impl LengthFilter {
+    /// The length that separates short strings from long ones when fortune is not given `-n`.
+    pub const DEFAULT_LENGTH: u32 = 160;
+
+    /// Whether a string of `len` bytes passes the filter.
+    pub fn matches(self, len: usize) -> bool {
+        match self {
+            LengthFilter::Any => true,
+            LengthFilter::Short(max) => len <= max as usize,
+            LengthFilter::Long(min) => len > min as usize,
+        }
+    }
+
+    /// Whether any string in the file described by `map` could pass the filter, judging only by
+    /// the header. When this is false, the file can be skipped without looking at its text.
+    pub fn may_match(self, map: &StrMap) -> bool {
+        !map.is_empty() && match self {
+            LengthFilter::Any => true,
+            LengthFilter::Short(max) => map.shortest() <= max,
+            LengthFilter::Long(min) => map.longest() > min,
+        }
+    }
+}
+
+impl StrMap {
+    /// Returns the indexes of the strings in `text` that pass `filter`, in index order.
+    pub fn filtered(&self, text: &[u8], filter: LengthFilter) -> Vec<usize> {
+        if !filter.may_match(self) {
+            return Vec::new();
+        }
+
+        (0..self.offsets.len())
+            .filter(|&idx| self.record(text, idx).is_some_and(|record| filter.matches(record.len())))
+            .collect()
+    }
+
+    /// Picks the index of a string that passes `filter` uniformly at random, or `None` if no string
+    /// in `text` does.
+    pub fn random_filtered<R: Rng + ?Sized>(&self, text: &[u8], filter: LengthFilter, rng: &mut R) -> Option<usize> {
+        if filter == LengthFilter::Any {
+            return self.random_index(rng);
+        }
+        self.filtered(text, filter).choose(rng).cloned()
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use std::io::Cursor;
+    use crate::StrMap;
+    use super::LengthFilter;
+
+    static SAMPLE: &[u8] = include_bytes!("../sample.txt");
+    static SAMPLE_DAT: &[u8] = include_bytes!("../sample.txt.dat");
+
+    fn sample() -> StrMap {
+        StrMap::read(&mut Cursor::new(SAMPLE_DAT)).unwrap()
+    }
+
+    #[test]
+    fn lengths_exclude_delimiter() {
+        let map = sample();
+
+        assert_eq!(vec![0], map.filtered(SAMPLE, LengthFilter::Short(17)));
+        assert_eq!(vec![3], map.filtered(SAMPLE, LengthFilter::Long(20)));
+        assert_eq!(vec![0, 1, 2, 3], map.filtered(SAMPLE, LengthFilter::Any));
+    }
+
+    #[test]
+    fn header_rules_out_files() {
+        let map = sample();
+
+        assert!(!LengthFilter::Short(16).may_match(&map));
+        assert!(LengthFilter::Short(17).may_match(&map));
+        assert!(!LengthFilter::Long(21).may_match(&map));
+        assert!(LengthFilter::Long(20).may_match(&map));
+    }
+
+    #[test]
+    fn skipped_files_are_not_read() {
+        assert!(sample().filtered(&[], LengthFilter::Long(21)).is_empty());
+    }
+
+    #[test]
+    fn random_filtered_only_picks_matches() {
+        let map = sample();
+        let mut rng = StdRng::seed_from_u64(11);
+
+        for _ in 0..20 {
+            assert_eq!(Some(3), map.random_filtered(SAMPLE, LengthFilter::Long(20), &mut rng));
+        }
+        assert_eq!(None, map.random_filtered(SAMPLE, LengthFilter::Short(2), &mut rng));
+    }
+}
@@ -0,0 +1,369 @@
+use alloc::vec::Vec;
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use core::fmt;
+
+use crate::{StrFlags, VERSION};
+
+/// The on-disk dialect of a strfile index.
+///
+/// strfile writes its header as a C struct, so the width of each field depends on the platform
+/// the index was built on. Every dialect keeps the delimiter in the first byte of its own field;
+/// the byte order of the values is described separately, by `Endianness`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(rename_all = "kebab-case"))]
+pub enum Layout {
+    /// Four-byte header fields and offsets, as written on 32-bit systems and by most ports.
+    X86,
+
+    /// Eight-byte fields, each holding a four-byte value followed by four bytes of padding, as
+    /// written by strfile builds on 64-bit systems that store the result of `htonl` in an
+    /// `unsigned long`.
+    X64,
+
+    /// Eight-byte fields, each holding a 64-bit integer. In big-endian files that means four bytes
+    /// of padding followed by a four-byte value; in little-endian files it is indistinguishable
+    /// from `X64`.
+    X64Wide,
+}
+
+/// The byte order of the values in an index.
+///
+/// strfile converts every value to network byte order before writing it, but some ports skip the
+/// conversion and write in host byte order, which is little-endian nearly everywhere.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(rename_all = "kebab-case"))]
+pub enum Endianness {
+    /// Most significant byte first, as strfile writes.
+    Big,
+
+    /// Least significant byte first.
+    Little,
+}
+
+impl Layout {
+    /// The length of the header, in bytes.
+    pub const fn header_len(self) -> u64 {
+        match self {
+            Layout::X86 => 24,
+            Layout::X64 | Layout::X64Wide => 48,
+        }
+    }
+
+    /// The width of each header field and offset, in bytes.
+    pub const fn stride(self) -> u64 {
+        match self {
+            Layout::X86 => 4,
+            Layout::X64 | Layout::X64Wide => 8,
+        }
+    }
+
+    /// The short name of the layout: `x86`, `x64` or `x64-wide`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Layout::X86 => "x86",
+            Layout::X64 => "x64",
+            Layout::X64Wide => "x64-wide",
+        }
+    }
+
+    /// Looks up a layout by the name returned from `name`.
+    pub fn from_name(name: &str) -> Option<Layout> {
+        match name {
+            "x86" => Some(Layout::X86),
+            "x64" => Some(Layout::X64),
+            "x64-wide" => Some(Layout::X64Wide),
+            _ => None,
+        }
+    }
+
+    /// Whether this layout uses eight-byte fields.
+    pub fn is_64bit(self) -> bool {
+        self != Layout::X86
+    }
+
+    /// Works out which layout an index uses, given its first `header_len()` bytes (or as many as
+    /// exist) and its total length. Any further bytes of the offset table help detection along.
+    ///
+    /// Both byte orders are considered; see `Endianness::detect` to find out which one the index
+    /// uses.
+    pub fn detect(data: &[u8], len: u64) -> Layout {
+        detect(data, len, &[Layout::X86, Layout::X64, Layout::X64Wide]).0
+    }
+
+    /// Scores how plausible the index in `data` is when read in this layout and byte order:
+    /// whether it yields a known version and known flags, whether its padding is empty, whether
+    /// `count + 1` offsets fit in the file (or fill it exactly), and whether the offsets present
+    /// in `data` increase.
+    fn score(self, endianness: Endianness, data: &[u8], len: u64) -> u32 {
+        let field = |idx| self.field(endianness, data, idx);
+        let mut score = 0;
+
+        if field(0).is_some_and(|version| version > 0 && version <= VERSION) {
+            score += 2;
+        }
+
+        if self.padding_is_empty(endianness, data) {
+            score += 1;
+        }
+
+        if let Some(count) = field(1) {
+            let expected = self.header_len() + (count as u64 + 1) * self.stride();
+            if expected == len {
+                score += 3;
+            } else if expected < len {
+                score += 1;
+            }
+        }
+
+        let flags = field(4).and_then(StrFlags::from_bits);
+        if let Some(flags) = flags {
+            score += 1;
+
+            // Only a table in file order is guaranteed to increase. Offsets start after the six
+            // header fields, and the first few are plenty.
+            let sequential = !flags.intersects(StrFlags::STR_RANDOM | StrFlags::STR_ORDERED);
+            let offsets: Vec<_> = (6..10).filter_map(field).collect();
+            if sequential && offsets.len() > 1 && offsets.windows(2).all(|pair| pair[0] <= pair[1]) {
+                score += 1;
+            }
+        }
+
+        score
+    }
+
+    /// Decodes the field at `idx`, counting header fields and then offsets, if `data` is long
+    /// enough to contain it.
+    fn field(self, endianness: Endianness, data: &[u8], idx: usize) -> Option<u32> {
+        let start = idx * self.stride() as usize + self.value_position(endianness);
+        data.get(start..start + 4).map(|bytes| endianness.read_u32(bytes))
+    }
+
+    fn padding_is_empty(self, endianness: Endianness, header: &[u8]) -> bool {
+        let delimiter_padding = match header.get(self.delimiter_position() + 1..self.header_len() as usize) {
+            Some(padding) => padding.iter().all(|&b| b == 0),
+            None => return false,
+        };
+        if self == Layout::X86 {
+            return delimiter_padding;
+        }
+
+        // In eight-byte fields, the padding is whichever half doesn't hold the value.
+        let pad = 4 - self.value_position(endianness);
+        delimiter_padding && (0..5).all(|idx| header.get(idx * 8 + pad..idx * 8 + pad + 4) == Some(&[0; 4][..]))
+    }
+
+    /// Whether offsets in this layout and byte order are 64-bit integers, able to index text
+    /// longer than 4 GiB. Elsewhere they are 32-bit values, padded out to the width of the layout.
+    pub fn has_64bit_offsets(self, endianness: Endianness) -> bool {
+        !matches!((self, endianness), (Layout::X86, _) | (Layout::X64, Endianness::Big))
+    }
+
+    /// The position of the four-byte value within each field.
+    pub(crate) fn value_position(self, endianness: Endianness) -> usize {
+        match (self, endianness) {
+            (Layout::X64Wide, Endianness::Big) => 4,
+            _ => 0,
+        }
+    }
+
+    /// The position of the delimiter byte within the header.
+    pub(crate) fn delimiter_position(self) -> usize {
+        match self {
+            Layout::X86 => 20,
+            Layout::X64 | Layout::X64Wide => 40,
+        }
+    }
+}
+
+impl fmt::Display for Layout {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl Endianness {
+    /// The short name of the byte order: `big` or `little`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Endianness::Big => "big",
+            Endianness::Little => "little",
+        }
+    }
+
+    /// Looks up a byte order by the name returned from `name`.
+    pub fn from_name(name: &str) -> Option<Endianness> {
+        match name {
+            "big" => Some(Endianness::Big),
+            "little" => Some(Endianness::Little),
+            _ => None,
+        }
+    }
+
+    /// Works out which byte order an index written in `layout` uses, given the same data as
+    /// `Layout::detect`. Ties go to big-endian, the order strfile writes.
+    pub fn detect(data: &[u8], len: u64, layout: Layout) -> Endianness {
+        detect(data, len, &[layout]).1
+    }
+
+    pub(crate) fn read_u32(self, buf: &[u8]) -> u32 {
+        match self {
+            Endianness::Big => BigEndian::read_u32(buf),
+            Endianness::Little => LittleEndian::read_u32(buf),
+        }
+    }
+
+    pub(crate) fn read_u64(self, buf: &[u8]) -> u64 {
+        match self {
+            Endianness::Big => BigEndian::read_u64(buf),
+            Endianness::Little => LittleEndian::read_u64(buf),
+        }
+    }
+
+    #[cfg(feature = "std")]
+    pub(crate) fn write_u32(self, buf: &mut [u8], value: u32) {
+        match self {
+            Endianness::Big => BigEndian::write_u32(buf, value),
+            Endianness::Little => LittleEndian::write_u32(buf, value),
+        }
+    }
+
+    #[cfg(feature = "std")]
+    pub(crate) fn write_u64(self, buf: &mut [u8], value: u64) {
+        match self {
+            Endianness::Big => BigEndian::write_u64(buf, value),
+            Endianness::Little => LittleEndian::write_u64(buf, value),
+        }
+    }
+}
+
+impl fmt::Display for Endianness {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Picks the most plausible of `layouts` in either byte order. The best score wins; ties go to
+/// the earlier layout, then to big-endian.
+pub(crate) fn detect(data: &[u8], len: u64, layouts: &[Layout]) -> (Layout, Endianness) {
+    let mut best = ((layouts[0], Endianness::Big), 0);
+
+    for &layout in layouts {
+        for &endianness in &[Endianness::Big, Endianness::Little] {
+            let score = layout.score(endianness, data, len);
+            if score > best.1 {
+                best = ((layout, endianness), score);
+            }
+        }
+    }
+
+    best.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Endianness, Layout};
+    use alloc::string::ToString;
+    use alloc::vec::Vec;
+
+    static SAMPLE_DAT: &[u8] = include_bytes!("../sample.txt.dat");
+    static SAMPLE_DAT_64: &[u8] = include_bytes!("../sample.txt-64.dat");
+
+    #[test]
+    fn detects_x86() {
+        assert_eq!(Layout::X86, Layout::detect(SAMPLE_DAT, SAMPLE_DAT.len() as u64));
+    }
+
+    #[test]
+    fn detects_x64() {
+        assert_eq!(Layout::X64, Layout::detect(SAMPLE_DAT_64, SAMPLE_DAT_64.len() as u64));
+    }
+
+    #[test]
+    fn detects_x64_wide() {
+        let dat = widen(SAMPLE_DAT_64);
+        assert_eq!(Layout::X64Wide, Layout::detect(&dat, dat.len() as u64));
+    }
+
+    #[test]
+    fn detects_x86_version_one() {
+        let mut dat = SAMPLE_DAT.to_vec();
+        dat[3] = 1;
+        assert_eq!(Layout::X86, Layout::detect(&dat, dat.len() as u64));
+    }
+
+    #[test]
+    fn detects_byte_order() {
+        assert_eq!(Endianness::Big, Endianness::detect(SAMPLE_DAT, SAMPLE_DAT.len() as u64, Layout::X86));
+
+        let dat = swap_words(SAMPLE_DAT);
+        assert_eq!(Layout::X86, Layout::detect(&dat, dat.len() as u64));
+        assert_eq!(Endianness::Little, Endianness::detect(&dat, dat.len() as u64, Layout::X86));
+
+        let dat = swap_words(SAMPLE_DAT_64);
+        assert_eq!(Layout::X64, Layout::detect(&dat, dat.len() as u64));
+        assert_eq!(Endianness::Little, Endianness::detect(&dat, dat.len() as u64, Layout::X64));
+    }
+
+    #[test]
+    fn offsets_settle_ambiguous_byte_order() {
+        // An empty header with a version of 0x01000001 reads the same either way round, so only
+        // the offsets can tell the byte orders apart.
+        let mut dat = vec![0; 24];
+        dat[0] = 1;
+        dat[3] = 1;
+        for &offset in &[0xffu32, 0x100] {
+            dat.extend_from_slice(&offset.to_le_bytes());
+        }
+
+        assert_eq!(Endianness::Big, Endianness::detect(&dat[..24], 100, Layout::X86));
+        assert_eq!(Endianness::Little, Endianness::detect(&dat, 100, Layout::X86));
+    }
+
+    #[test]
+    fn only_integer_fields_hold_64bit_offsets() {
+        assert!(!Layout::X86.has_64bit_offsets(Endianness::Big));
+        assert!(!Layout::X86.has_64bit_offsets(Endianness::Little));
+        assert!(!Layout::X64.has_64bit_offsets(Endianness::Big));
+        assert!(Layout::X64.has_64bit_offsets(Endianness::Little));
+        assert!(Layout::X64Wide.has_64bit_offsets(Endianness::Big));
+    }
+
+    #[test]
+    fn names_round_trip() {
+        for &layout in &[Layout::X86, Layout::X64, Layout::X64Wide] {
+            assert_eq!(Some(layout), Layout::from_name(&layout.to_string()));
+        }
+        assert_eq!(None, Layout::from_name("x32"));
+
+        for &endianness in &[Endianness::Big, Endianness::Little] {
+            assert_eq!(Some(endianness), Endianness::from_name(&endianness.to_string()));
+        }
+        assert_eq!(None, Endianness::from_name("middle"));
+    }
+
+    /// Reverses the bytes of every four-byte word but those holding the delimiter.
+    fn swap_words(dat: &[u8]) -> Vec<u8> {
+        let delimiter = if dat == SAMPLE_DAT { 5 } else { 10 };
+        let mut dat = dat.to_vec();
+        for (idx, word) in dat.chunks_mut(4).enumerate() {
+            if idx != delimiter {
+                word.reverse();
+            }
+        }
+        dat
+    }
+
+    /// Swaps the value and padding words of every 64-bit slot but the delimiter.
+    fn widen(dat: &[u8]) -> Vec<u8> {
+        let mut dat = dat.to_vec();
+        for (idx, slot) in dat.chunks_mut(8).enumerate() {
+            if idx != 5 {
+                let (left, right) = slot.split_at_mut(4);
+                left.swap_with_slice(right);
+            }
+        }
+        dat
+    }
+}
@@ -0,0 +1,239 @@
+use rand::Rng;
+use std::io::{self, SeekFrom};
+
+use crate::{detect_format, read_header, read_offset, Endianness, Header, Layout, StrFlags, StrMapError};
+
+/// An index that reads only its header up front and fetches offsets from the source on demand.
+///
+/// Where `StrMap::read` loads the whole offset table, `LazyStrMap` seeks straight to the entries
+/// for string `i` when asked, which makes picking one string from a very large index cheap.
+#[derive(Debug)]
+pub struct LazyStrMap<T> {
+    source: T,
+    header: Header,
+    layout: Layout,
+    endianness: Endianness,
+    table: u64,
+}
+
+impl<T: io::Read + io::Seek> LazyStrMap<T> {
+    /// Reads the header of the index in `source`, detecting its layout and byte order.
+    pub fn new(mut source: T) -> Result<LazyStrMap<T>, StrMapError> {
+        let (layout, endianness) = detect_format(&mut source, None)?;
+        LazyStrMap::with_layout_endian(source, layout, endianness)
+    }
+
+    /// Reads the header of the index in `source`, which was written in the given layout,
+    /// detecting its byte order.
+    pub fn with_layout(mut source: T, layout: Layout) -> Result<LazyStrMap<T>, StrMapError> {
+        let (_, endianness) = detect_format(&mut source, Some(layout))?;
+        LazyStrMap::with_layout_endian(source, layout, endianness)
+    }
+
+    /// Reads the header of the index in `source`, which was written in the given layout and byte
+    /// order.
+    ///
+    /// The source must be long enough to hold every offset the header promises; the offsets
+    /// themselves are not read until needed.
+    pub fn with_layout_endian(
+        mut source: T,
+        layout: Layout,
+        endianness: Endianness,
+    ) -> Result<LazyStrMap<T>, StrMapError> {
+        let header = read_header(&mut source, layout, endianness)?;
+        let table = source.stream_position()?;
+
+        let len = source.seek(SeekFrom::End(0))?;
+        let available = len.saturating_sub(table) / layout.stride();
+        if available < header.count as u64 + 1 {
+            return Err(StrMapError::CountMismatch {
+                position: len,
+                expected: header.count,
+                actual: available.saturating_sub(1) as u32,
+            });
+        }
+
+        Ok(LazyStrMap {
+            source,
+            header,
+            layout,
+            endianness,
+            table,
+        })
+    }
+
+    /// Returns the offsets of the string at `idx`, or `None` if there is no such string.
+    ///
+    /// This costs two seeks and two reads, regardless of the size of the index. In an ordered or
+    /// randomized index, the end offset given is the end of the text, as with `StrMapRef::get`.
+    pub fn get(&mut self, idx: usize) -> Result<Option<(u64, u64)>, StrMapError> {
+        let count = self.header.count as usize;
+        if idx >= count {
+            return Ok(None);
+        }
+
+        let sequential = !self.header.flags.intersects(StrFlags::STR_RANDOM | StrFlags::STR_ORDERED);
+        let end = if sequential { idx + 1 } else { count };
+
+        let stride = self.layout.stride();
+        let start = read_offset(&mut self.source, self.layout, self.endianness, self.table + idx as u64 * stride)?;
+        let end = read_offset(&mut self.source, self.layout, self.endianness, self.table + end as u64 * stride)?;
+        Ok(Some((start, end)))
+    }
+
+    /// Picks the offsets of a string uniformly at random, or `None` if the index is empty.
+    pub fn random<R: Rng + ?Sized>(&mut self, rng: &mut R) -> Result<Option<(u64, u64)>, StrMapError> {
+        if self.header.count == 0 {
+            return Ok(None);
+        }
+        let idx = rng.gen_range(0..self.header.count as usize);
+        self.get(idx)
+    }
+
+    /// Releases the underlying source.
+    pub fn into_inner(self) -> T {
+        self.source
+    }
+}
+
+impl<T> LazyStrMap<T> {
+    /// The layout of the index.
+    pub fn layout(&self) -> Layout {
+        self.layout
+    }
+
+    /// The byte order of the index.
+    pub fn endianness(&self) -> Endianness {
+        self.endianness
+    }
+
+    /// The header of the index.
+    pub fn header(&self) -> Header {
+        self.header
+    }
+
+    /// The strfile version recorded in the header.
+    pub fn version(&self) -> u32 {
+        self.header.version
+    }
+
+    /// The number of strings contained in the mapped file.
+    pub fn len(&self) -> u32 {
+        self.header.count
+    }
+
+    /// Whether the mapped file contains no strings at all.
+    pub fn is_empty(&self) -> bool {
+        self.header.count == 0
+    }
+
+    /// The longest string contained in the mapped file.
+    pub fn longest(&self) -> u32 {
+        self.header.longest
+    }
+
+    /// The shortest string contained in the mapped file.
+    pub fn shortest(&self) -> u32 {
+        self.header.shortest
+    }
+
+    /// The delimiter used in the mapped file.
+    pub fn delimiter(&self) -> u8 {
+        self.header.delimiter
+    }
+
+    /// Whether the index for this file is randomized.
+    pub fn is_random(&self) -> bool {
+        self.header.flags.contains(StrFlags::STR_RANDOM)
+    }
+
+    /// Whether the index for this file is sorted.
+    pub fn is_ordered(&self) -> bool {
+        self.header.flags.contains(StrFlags::STR_ORDERED)
+    }
+
+    /// Whether the contents of this file have been rotated via rot13.
+    pub fn is_rotated(&self) -> bool {
+        self.header.flags.contains(StrFlags::STR_ROTATED)
+    }
+
+    /// Whether this file contains comment lines, which begin with two delimiters.
+    pub fn has_comments(&self) -> bool {
+        self.header.flags.contains(StrFlags::STR_COMMENTS)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use crate::{Endianness, Layout, StrMap, StrMapError, StrMapRef};
+    use super::LazyStrMap;
+
+    static SAMPLE_DAT: &[u8] = include_bytes!("../sample.txt.dat");
+    static SAMPLE_DAT_64: &[u8] = include_bytes!("../sample.txt-64.dat");
+
+    fn matches_eager(dat: &[u8]) {
+        let eager = StrMap::read(&mut Cursor::new(dat)).unwrap();
+        let mut lazy = LazyStrMap::new(Cursor::new(dat)).unwrap();
+
+        assert_eq!(eager.len(), lazy.len());
+        assert_eq!(eager.longest(), lazy.longest());
+        assert_eq!(eager.layout(), lazy.layout());
+        assert_eq!(eager.endianness(), lazy.endianness());
+        for idx in (0..eager.len() as usize).rev() {
+            assert_eq!(eager.get(idx), lazy.get(idx).unwrap());
+        }
+        assert_eq!(None, lazy.get(eager.len() as usize).unwrap());
+    }
+
+    #[test]
+    fn x86_matches_eager_read() {
+        matches_eager(SAMPLE_DAT);
+    }
+
+    #[test]
+    fn x64_matches_eager_read() {
+        matches_eager(SAMPLE_DAT_64);
+    }
+
+    #[test]
+    fn ordered_and_shuffled_match_eager_read() {
+        use rand::rngs::StdRng;
+        use rand::SeedableRng;
+        use crate::StrMapBuilder;
+
+        let text = b"d\n%\nbb\n%\n%\nccc\n%\na\n%\n";
+        let ordered = StrMapBuilder::new().ordered(true).build_bytes(text).unwrap();
+        let mut shuffled = StrMapBuilder::new().build_bytes(text).unwrap();
+        shuffled.shuffle(&mut StdRng::seed_from_u64(7));
+
+        for map in &[ordered, shuffled] {
+            let mut dat = Vec::new();
+            map.write(&mut dat).unwrap();
+            let borrowed = StrMapRef::new(&dat).unwrap();
+            let mut lazy = LazyStrMap::new(Cursor::new(&dat)).unwrap();
+
+            for idx in 0..map.len() as usize {
+                assert_eq!(borrowed.get(idx), lazy.get(idx).unwrap());
+                assert_eq!(map.get(idx).map(|(start, _)| start), borrowed.get(idx).map(|(start, _)| start));
+            }
+        }
+    }
+
+    #[test]
+    fn little_endian_matches_eager_read() {
+        let mut dat = Vec::new();
+        let map = StrMap::read(&mut Cursor::new(SAMPLE_DAT_64)).unwrap();
+        map.write_layout_endian(&mut dat, Layout::X64, Endianness::Little).unwrap();
+        matches_eager(&dat);
+    }
+
+    #[test]
+    fn short_table_is_rejected_up_front() {
+        let dat = &SAMPLE_DAT[..SAMPLE_DAT.len() - 4];
+        match LazyStrMap::with_layout(Cursor::new(dat), Layout::X86) {
+            Err(StrMapError::CountMismatch { expected: 4, actual: 3, .. }) => (),
+            other => panic!("unexpected result: {:?}", other.map(|_| ())),
+        }
+    }
+}
@@ -1,18 +1,98 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+#[cfg(all(test, not(feature = "std")))]
+#[macro_use]
+extern crate std;
 #[macro_use] extern crate bitflags;
 extern crate byteorder;
+#[cfg(feature = "std")]
+extern crate memchr;
+extern crate rand;
+#[cfg(feature = "rayon")]
+extern crate rayon;
+#[cfg(feature = "std")]
+extern crate regex;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "tokio")]
+extern crate tokio;
+
+#[cfg(feature = "tokio")]
+mod asynchronous;
+mod borrowed;
+#[cfg(feature = "std")]
+mod builder;
+#[cfg(feature = "std")]
+mod collection;
+mod comments;
+#[cfg(feature = "std")]
+mod cookie;
+#[cfg(feature = "std")]
+mod dump;
+mod error;
+mod filter;
+mod layout;
+#[cfg(feature = "std")]
+mod lazy;
+mod order;
+mod rot13;
+#[cfg(feature = "std")]
+mod scan;
+#[cfg(feature = "std")]
+mod search;
+#[cfg(feature = "serde")]
+mod serde_impl;
+#[cfg(feature = "std")]
+mod unstr;
+#[cfg(feature = "std")]
+mod validate;
 
+use alloc::vec::{self, Vec};
+use core::slice;
+use rand::Rng;
+#[cfg(feature = "std")]
 use std::io;
-use std::slice;
-use std::vec;
+
+pub use borrowed::{StrMapRef, StrMapRefIter};
+#[cfg(feature = "std")]
+pub use builder::StrMapBuilder;
+#[cfg(feature = "std")]
+pub use collection::Collection;
+pub use comments::strip_comments;
+#[cfg(feature = "std")]
+pub use cookie::{dat_path, CookieFile, CookieFileIter};
+#[cfg(feature = "std")]
+pub use dump::DumpFormat;
+pub use error::StrMapError;
+pub use filter::LengthFilter;
+pub use layout::{Endianness, Layout};
+#[cfg(feature = "std")]
+pub use lazy::LazyStrMap;
+pub use rot13::{rot13, rot13_in_place};
+#[cfg(feature = "std")]
+pub use search::{write_matches, Match, Search};
+#[cfg(feature = "std")]
+pub use validate::{Discrepancy, ValidationReport};
 
 bitflags! {
-    flags StrFlags: u32 {
-        const STR_RANDOM = 0b00000001,
-        const STR_ORDERED = 0b00000010,
-        const STR_ROTATED = 0b00000100,
+    struct StrFlags: u32 {
+        const STR_RANDOM = 0b00000001;
+        const STR_ORDERED = 0b00000010;
+        const STR_ROTATED = 0b00000100;
+        const STR_COMMENTS = 0b00001000;
     }
 }
 
+/// Each flag alongside its name, as strfile's header spells it.
+#[cfg(any(feature = "std", feature = "serde"))]
+static FLAG_NAMES: &[(StrFlags, &str)] = &[
+    (StrFlags::STR_RANDOM, "STR_RANDOM"),
+    (StrFlags::STR_ORDERED, "STR_ORDERED"),
+    (StrFlags::STR_ROTATED, "STR_ROTATED"),
+    (StrFlags::STR_COMMENTS, "STR_COMMENTS"),
+];
+
 #[derive(Debug)]
 pub struct StrMap {
     version: u32,
@@ -21,60 +101,189 @@ pub struct StrMap {
     shortest: u32,
     flags: StrFlags,
     delimiter: u8,
-    offsets: Vec<(u32, u32)>,
+    layout: Layout,
+    endianness: Endianness,
+    offsets: Vec<(u64, u64)>,
 }
 
 impl StrMap {
-    pub fn read<T: io::Read + io::Seek>(s: &mut T) -> io::Result<StrMap> {
-        use byteorder::{NetworkEndian, ReadBytesExt};
-        use std::io::SeekFrom;
-
-        let version = s.read_u32::<NetworkEndian>()?;
+    /// Reads an index, detecting whether it was written with 32-bit or 64-bit fields and in which
+    /// byte order.
+    #[cfg(feature = "std")]
+    pub fn read<T: io::Read + io::Seek>(s: &mut T) -> Result<StrMap, StrMapError> {
+        let (layout, endianness) = detect_format(s, None)?;
+        StrMap::read_layout_endian(s, layout, endianness)
+    }
 
-        // I have this harebrained idea that x64 systems will mark the file with this
-        // "version 1" thing to let us know that the file was created on x64 and is,
-        // therefore, fucked.
-        if version == 1 {
-            return _x64_read(s);
-        }
+    /// Reads an index written in the given layout, bypassing layout detection. The byte order is
+    /// still detected.
+    #[cfg(feature = "std")]
+    pub fn read_layout<T: io::Read + io::Seek>(s: &mut T, layout: Layout) -> Result<StrMap, StrMapError> {
+        let (_, endianness) = detect_format(s, Some(layout))?;
+        StrMap::read_layout_endian(s, layout, endianness)
+    }
 
-        let count = s.read_u32::<NetworkEndian>()?;
-        let longest = s.read_u32::<NetworkEndian>()?;
-        let shortest = s.read_u32::<NetworkEndian>()?;
-        let flags = StrFlags::from_bits(s.read_u32::<NetworkEndian>()?).expect("well, dammit");
-        let delimiter = s.read_u8()?;
+    /// Reads an index written in the given layout and byte order, bypassing detection entirely.
+    #[cfg(feature = "std")]
+    pub fn read_layout_endian<T: io::Read + io::Seek>(
+        s: &mut T,
+        layout: Layout,
+        endianness: Endianness,
+    ) -> Result<StrMap, StrMapError> {
+        let header = read_header(s, layout, endianness)?;
+        let offsets = read_offsets(s, header.count, header.flags, layout, endianness)?;
 
-        // We need to read one additional value to get valid offsets, because each 
-        // offset consists of a pairing of two offset values--hence our range is 
-        // `0..(count + 1)`. We begin by skipping the next three bytes, since a 
-        // delimiter consists of one byte only.
-        s.seek(SeekFrom::Current(3))?;
-        let values: Vec<_> = (0..(count + 1)).filter_map(|_| s.read_u32::<NetworkEndian>().ok()).collect();
-        let offsets: Vec<_> = OffsetsIter::new(values.iter().cloned()).collect();
+        Ok(StrMap {
+            version: header.version,
+            count: header.count,
+            longest: header.longest,
+            shortest: header.shortest,
+            flags: header.flags,
+            delimiter: header.delimiter,
+            layout,
+            endianness,
+            offsets,
+        })
+    }
 
-        if count as usize != offsets.len() {
-            return Err(io::Error::new(
-                io::ErrorKind::Other,
-                format!("Str count in header does not match str count in data: Header({}) vs Data({})", count, offsets.len())
-            ));
+    /// Assembles an index from a header and its offset table: the offset of each string in index
+    /// order followed by the offset of the end of the text, exactly as they would appear on disk.
+    ///
+    /// The offsets are checked the same way `StrMap::read` checks them, and errors report
+    /// positions as though the index had been written in `layout`.
+    pub fn from_parts(header: Header, layout: Layout, offsets: Vec<u64>) -> Result<StrMap, StrMapError> {
+        let position = |idx: usize| layout.header_len() + idx as u64 * layout.stride();
+        if header.count as usize + 1 != offsets.len() {
+            return Err(StrMapError::CountMismatch {
+                position: position(offsets.len()),
+                expected: header.count,
+                actual: offsets.len().saturating_sub(1) as u32,
+            });
         }
+        check_offsets(offsets.len(), |idx| offsets[idx], header.flags, position)?;
 
         Ok(StrMap {
-            version: version,
-            count: count,
-            longest: longest,
-            shortest: shortest,
-            flags: flags,
-            delimiter: delimiter,
-            offsets: offsets,
+            version: header.version,
+            count: header.count,
+            longest: header.longest,
+            shortest: header.shortest,
+            flags: header.flags,
+            delimiter: header.delimiter,
+            layout,
+            endianness: Endianness::Big,
+            offsets: order::pair_offsets(offsets, header.flags),
         })
     }
 
+    /// The header of this index.
+    pub fn header(&self) -> Header {
+        Header {
+            version: self.version,
+            count: self.count,
+            longest: self.longest,
+            shortest: self.shortest,
+            flags: self.flags,
+            delimiter: self.delimiter,
+        }
+    }
+
+    /// The offset table as it would appear on disk: the offset of each string in index order,
+    /// followed by the offset of the end of the text.
+    pub fn offsets(&self) -> Vec<u64> {
+        let mut offsets: Vec<_> = self.offsets.iter().map(|&(start, _)| start).collect();
+        offsets.push(self.end());
+        offsets
+    }
+
+    /// Writes this index in the 32-bit strfile format: a header of big-endian `u32` fields
+    /// followed by the offset of each string and, finally, the offset of the end of the text.
+    #[cfg(feature = "std")]
+    pub fn write<T: io::Write>(&self, s: &mut T) -> io::Result<()> {
+        self.write_layout(s, Layout::X86)
+    }
+
+    /// Writes this index in the given layout, padding each field as that layout requires. The
+    /// result can be read back with `StrMap::read_layout` or detected by `StrMap::read`.
+    ///
+    /// Text longer than 4 GiB can only be indexed in a layout with 64-bit offsets; anything else
+    /// fails with `InvalidInput` before writing a byte. See `Layout::has_64bit_offsets`.
+    #[cfg(feature = "std")]
+    pub fn write_layout<T: io::Write>(&self, s: &mut T, layout: Layout) -> io::Result<()> {
+        self.write_layout_endian(s, layout, Endianness::Big)
+    }
+
+    /// Writes this index in the given layout and byte order. Only ports of strfile that skip the
+    /// conversion to network byte order produce little-endian indexes, so prefer `write_layout`
+    /// unless something needs one.
+    #[cfg(feature = "std")]
+    pub fn write_layout_endian<T: io::Write>(
+        &self,
+        s: &mut T,
+        layout: Layout,
+        endianness: Endianness,
+    ) -> io::Result<()> {
+        if self.end() > u32::MAX as u64 && !layout.has_64bit_offsets(endianness) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!(
+                "{} bytes of text cannot be indexed with the 32-bit offsets of the {} layout in {}-endian order",
+                self.end(),
+                layout,
+                endianness,
+            )));
+        }
+
+        write_field(s, layout, endianness, self.version as u64)?;
+        write_field(s, layout, endianness, self.count as u64)?;
+        write_field(s, layout, endianness, self.longest as u64)?;
+        write_field(s, layout, endianness, self.shortest as u64)?;
+        write_field(s, layout, endianness, self.flags.bits() as u64)?;
+
+        let mut delimiter = vec![0; layout.stride() as usize];
+        delimiter[0] = self.delimiter;
+        s.write_all(&delimiter)?;
+
+        for offset in self.offsets() {
+            write_field(s, layout, endianness, offset)?;
+        }
+        Ok(())
+    }
+
+    /// Checks this index against `text`, the cookie file it is supposed to describe, and reports
+    /// every way in which the two disagree. An index with discrepancies should be rebuilt.
+    #[cfg(feature = "std")]
+    pub fn validate(&self, text: &[u8]) -> ValidationReport {
+        validate::validate(self, text)
+    }
+
+    /// The final offset in the index, which marks the end of the text.
+    fn end(&self) -> u64 {
+        self.offsets.iter().map(|&(_, end)| end).max().unwrap_or(0)
+    }
+
+    /// The layout this index was read from. Indexes built in memory use `Layout::X86`.
+    pub fn layout(&self) -> Layout {
+        self.layout
+    }
+
+    /// The byte order this index was read in. Indexes built in memory use `Endianness::Big`.
+    pub fn endianness(&self) -> Endianness {
+        self.endianness
+    }
+
+    /// The strfile version recorded in the header.
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
     /// The number of strings contained in the mapped file.
     pub fn len(&self) -> u32 {
         self.count
     }
 
+    /// Whether the mapped file contains no strings at all.
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
     /// The longest string contained in the mapped file.
     pub fn longest(&self) -> u32 {
         self.longest
@@ -92,81 +301,414 @@ impl StrMap {
 
     /// Whether the index for this file is randomized.
     pub fn is_random(&self) -> bool {
-        self.flags.contains(STR_RANDOM)
+        self.flags.contains(StrFlags::STR_RANDOM)
     }
 
     /// Whether the index for this file is sorted.
     pub fn is_ordered(&self) -> bool {
-        self.flags.contains(STR_ORDERED)
+        self.flags.contains(StrFlags::STR_ORDERED)
     }
 
     /// Whether the contents of this file have been rotated via rot13.
     pub fn is_rotated(&self) -> bool {
-        self.flags.contains(STR_ROTATED)
+        self.flags.contains(StrFlags::STR_ROTATED)
+    }
+
+    /// Whether this file contains comment lines, which begin with two delimiters. See
+    /// `strip_comments`.
+    pub fn has_comments(&self) -> bool {
+        self.flags.contains(StrFlags::STR_COMMENTS)
     }
 
     /// Returns an iterator over the string offsets contained in this index.
-    pub fn iter(&self) -> StrMapIter {
+    pub fn iter(&self) -> StrMapIter<'_> {
         StrMapIter {
             source: self.offsets.iter()
         }
     }
+
+    /// Returns the offsets of the string at `idx`, if there is one.
+    pub fn get(&self, idx: usize) -> Option<(u64, u64)> {
+        self.offsets.get(idx).cloned()
+    }
+
+    /// Picks the index of a string uniformly at random, or `None` if the index is empty.
+    pub fn random_index<R: Rng + ?Sized>(&self, rng: &mut R) -> Option<usize> {
+        if self.offsets.is_empty() {
+            return None;
+        }
+        Some(rng.gen_range(0..self.offsets.len()))
+    }
+
+    /// Picks the offsets of a string uniformly at random, or `None` if the index is empty.
+    pub fn random<R: Rng + ?Sized>(&self, rng: &mut R) -> Option<(u64, u64)> {
+        self.random_index(rng).and_then(|idx| self.get(idx))
+    }
+
+    /// Extracts the string at `idx` from `text`, the cookie file described by this index.
+    ///
+    /// The delimiter line that ends the string is not included, but any comment lines are; see
+    /// `strip_comments`. Returns `None` if there is no string at `idx` or if `text` is too short
+    /// to contain it.
+    pub fn record<'a>(&self, text: &'a [u8], idx: usize) -> Option<&'a [u8]> {
+        let (start, end) = self.get(idx)?;
+        extract(text, start, end, self.delimiter)
+    }
 }
 
-fn _x64_read<T: io::Read + io::Seek>(s: &mut T) -> io::Result<StrMap> {
-    use byteorder::{NetworkEndian, ReadBytesExt};
-    use std::io::SeekFrom;
-    
-    s.seek(SeekFrom::Current(4))?;
-    let count = s.read_u32::<NetworkEndian>()?;
-
-    s.seek(SeekFrom::Current(4))?;
-    let longest = s.read_u32::<NetworkEndian>()?;
-
-    s.seek(SeekFrom::Current(4))?;
-    let shortest = s.read_u32::<NetworkEndian>()?;
-
-    s.seek(SeekFrom::Current(4))?;
-    let flags = StrFlags::from_bits(s.read_u32::<NetworkEndian>()?).expect("well, dammit");
-
-    s.seek(SeekFrom::Current(4))?;
-    let delimiter = s.read_u8()?;
-
-    // For this case, we skip every second record because strfile is worthless
-    // on x64 systems. I almost stopped typing at "worthless," but I am nice.
-    s.seek(SeekFrom::Current(7))?;
-    let values: Vec<_> = (0..(count + 1))
-        .filter_map(|_| {
-            let ret = s.read_u32::<NetworkEndian>().ok();
-            match s.seek(SeekFrom::Current(4)) {
-                Err(_) => None,
-                _ => ret,
-            }
+/// Extracts the text of a single string from `text[start..end]`.
+///
+/// The string runs until the first delimiter line or the end of the range, whichever comes first.
+/// As in `StrMapBuilder`, a delimiter line is the delimiter byte followed by a newline, so a bare
+/// delimiter at the very end of the text is part of the string.
+/// Delimiter lines at the very beginning of the range are skipped; strfile leaves them there when
+/// it folds an empty string into the one that follows it.
+fn extract(text: &[u8], start: u64, end: u64, delimiter: u8) -> Option<&[u8]> {
+    let start = usize::try_from(start).ok()?;
+    let end = usize::try_from(end).unwrap_or(usize::MAX);
+    let mut record = text.get(start..end.min(text.len()))?;
+    while record.starts_with(&[delimiter, b'\n']) {
+        record = &record[2..];
+    }
+
+    let mut line = 0;
+    while line < record.len() {
+        let next = record[line..].iter().position(|&b| b == b'\n').map_or(record.len(), |n| line + n + 1);
+        if record[line..next] == [delimiter, b'\n'] {
+            return Some(&record[..line]);
+        }
+        line = next;
+    }
+
+    Some(record)
+}
+
+/// The newest strfile version we know how to read.
+const VERSION: u32 = 2;
+
+/// The fixed-size portion of an index, preceding the offset table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Header {
+    version: u32,
+    count: u32,
+    longest: u32,
+    shortest: u32,
+    flags: StrFlags,
+    delimiter: u8,
+}
+
+impl Header {
+    /// Assembles a header from its fields, checking the version and flags the same way
+    /// `StrMap::read` does. Errors report positions as though the header began a 32-bit index.
+    pub fn new(
+        version: u32,
+        count: u32,
+        longest: u32,
+        shortest: u32,
+        flags: u32,
+        delimiter: u8,
+    ) -> Result<Header, StrMapError> {
+        check_version(version, 0)?;
+        let flags = StrFlags::from_bits(flags).ok_or(StrMapError::UnknownFlags {
+            position: 16,
+            expected: StrFlags::all().bits(),
+            actual: flags,
+        })?;
+
+        Ok(Header {
+            version,
+            count,
+            longest,
+            shortest,
+            flags,
+            delimiter,
         })
-        .collect();
+    }
+
+    /// The strfile version.
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// The number of strings in the file.
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    /// The length of the longest string in the file.
+    pub fn longest(&self) -> u32 {
+        self.longest
+    }
+
+    /// The length of the shortest string in the file.
+    pub fn shortest(&self) -> u32 {
+        self.shortest
+    }
+
+    /// The raw flags field.
+    pub fn flags(&self) -> u32 {
+        self.flags.bits()
+    }
+
+    /// The delimiter used in the file.
+    pub fn delimiter(&self) -> u8 {
+        self.delimiter
+    }
+
+    /// Whether the index is randomized.
+    pub fn is_random(&self) -> bool {
+        self.flags.contains(StrFlags::STR_RANDOM)
+    }
+
+    /// Whether the index is sorted.
+    pub fn is_ordered(&self) -> bool {
+        self.flags.contains(StrFlags::STR_ORDERED)
+    }
+
+    /// Whether the file has been rotated via rot13.
+    pub fn is_rotated(&self) -> bool {
+        self.flags.contains(StrFlags::STR_ROTATED)
+    }
+
+    /// Whether the file contains comment lines.
+    pub fn has_comments(&self) -> bool {
+        self.flags.contains(StrFlags::STR_COMMENTS)
+    }
+}
 
-    let offsets: Vec<_> = OffsetsIter::new(values.iter().cloned()).collect();
+/// Works out the layout and byte order of the index starting at the current position, leaving the
+/// position where it was. If `layout` is given, only the byte order is detected.
+#[cfg(feature = "std")]
+fn detect_format<T: io::Read + io::Seek>(
+    s: &mut T,
+    layout: Option<Layout>,
+) -> Result<(Layout, Endianness), StrMapError> {
+    use std::io::{Read, SeekFrom};
 
-    if count as usize != offsets.len() {
-        return Err(io::Error::new(
-            io::ErrorKind::Other,
-            format!("Str count in header does not match str count in data: Header({}) vs Data({})", count, offsets.len())
-        ));
+    let start = s.stream_position()?;
+    let len = s.seek(SeekFrom::End(0))? - start;
+    s.seek(SeekFrom::Start(start))?;
+
+    let mut data = Vec::new();
+    s.by_ref().take(DETECT_LEN).read_to_end(&mut data)?;
+    s.seek(SeekFrom::Start(start))?;
+
+    Ok(detect(&data, len, layout))
+}
+
+/// How much of an index `detect` wants to see: the largest header, plus a few offsets to help
+/// tell byte orders apart.
+#[cfg(feature = "std")]
+const DETECT_LEN: u64 = Layout::X64.header_len() + 4 * Layout::X64.stride();
+
+/// Works out the layout and byte order of the index of `len` bytes beginning with `data`. If
+/// `layout` is given, only the byte order is detected.
+#[cfg(feature = "std")]
+fn detect(data: &[u8], len: u64, layout: Option<Layout>) -> (Layout, Endianness) {
+    match layout {
+        Some(layout) => (layout, Endianness::detect(data, len, layout)),
+        None => layout::detect(data, len, &[Layout::X86, Layout::X64, Layout::X64Wide]),
     }
+}
+
+/// Reads the header, leaving the position at the start of the offset table.
+#[cfg(feature = "std")]
+fn read_header<T: io::Read + io::Seek>(
+    s: &mut T,
+    layout: Layout,
+    endianness: Endianness,
+) -> Result<Header, StrMapError> {
+    use std::io::{Read, SeekFrom};
 
-    Ok(StrMap {
-        version: 1, // This is how we got here, remember?
-        count: count,
-        longest: longest,
-        shortest: shortest,
-        flags: flags,
-        delimiter: delimiter,
-        offsets: offsets,
+    let start = s.stream_position()?;
+    let mut data = Vec::new();
+    s.by_ref().take(layout.header_len()).read_to_end(&mut data)?;
+    let header = decode_header(&data, layout, endianness).map_err(|e| e.offset_by(start))?;
+
+    s.seek(SeekFrom::Start(start + layout.header_len()))?;
+    Ok(header)
+}
+
+/// Decodes the header at the start of `data`, reporting errors at positions relative to it.
+fn decode_header(data: &[u8], layout: Layout, endianness: Endianness) -> Result<Header, StrMapError> {
+    let truncated = |position| StrMapError::TruncatedHeader {
+        position,
+        expected: layout.header_len(),
+        actual: data.len() as u64,
+    };
+
+    // Each field is a `u32`, possibly padded out to the width of the layout.
+    let field = |idx: u64| {
+        let position = idx * layout.stride();
+        let start = position as usize + layout.value_position(endianness);
+        data.get(start..start + 4).map(|bytes| endianness.read_u32(bytes)).ok_or_else(|| truncated(position))
+    };
+
+    let version = field(0)?;
+    check_version(version, 0)?;
+    let count = field(1)?;
+    let longest = field(2)?;
+    let shortest = field(3)?;
+
+    let bits = field(4)?;
+    let flags = StrFlags::from_bits(bits).ok_or(StrMapError::UnknownFlags {
+        position: 4 * layout.stride(),
+        expected: StrFlags::all().bits(),
+        actual: bits,
+    })?;
+
+    // The delimiter is stored as the first byte of an otherwise empty field.
+    let position = layout.delimiter_position();
+    let delimiter = *data.get(position).ok_or_else(|| truncated(position as u64))?;
+
+    Ok(Header {
+        version,
+        count,
+        longest,
+        shortest,
+        flags,
+        delimiter,
     })
 }
 
+/// Writes a single header field or offset, along with whatever padding the layout puts around it.
+/// The value must fit in the field; see `Layout::has_64bit_offsets`.
+#[cfg(feature = "std")]
+fn write_field<T: io::Write>(s: &mut T, layout: Layout, endianness: Endianness, value: u64) -> io::Result<()> {
+    let mut field = [0; 8];
+    if layout.has_64bit_offsets(endianness) {
+        endianness.write_u64(&mut field, value);
+    } else {
+        let start = layout.value_position(endianness);
+        endianness.write_u32(&mut field[start..start + 4], value as u32);
+    }
+    s.write_all(&field[..layout.stride() as usize])
+}
+
+fn check_version(version: u32, position: u64) -> Result<(), StrMapError> {
+    if version == 0 || version > VERSION {
+        return Err(StrMapError::UnsupportedVersion {
+            position,
+            expected: VERSION,
+            actual: version,
+        });
+    }
+    Ok(())
+}
+
+/// Reads the single offset stored at `position`.
+#[cfg(feature = "std")]
+fn read_offset<T: io::Read + io::Seek>(
+    s: &mut T,
+    layout: Layout,
+    endianness: Endianness,
+    position: u64,
+) -> io::Result<u64> {
+    use std::io::SeekFrom;
+
+    let mut field = [0; 8];
+    let field = &mut field[..layout.stride() as usize];
+    s.seek(SeekFrom::Start(position))?;
+    s.read_exact(field)?;
+    Ok(decode_offset(field, layout, endianness))
+}
+
+/// Decodes an offset from its field, which must be `layout.stride()` bytes long. Only some
+/// layouts have room for the high word; in the rest, the value is padded out from 32 bits.
+fn decode_offset(field: &[u8], layout: Layout, endianness: Endianness) -> u64 {
+    if layout.has_64bit_offsets(endianness) {
+        endianness.read_u64(field)
+    } else {
+        let start = layout.value_position(endianness);
+        endianness.read_u32(&field[start..start + 4]) as u64
+    }
+}
+
+/// Reads the offset table following the header and pairs up offsets.
+///
+/// We need to read one additional value to get valid offsets, because each offset consists of a
+/// pairing of two offset values--hence we read `count + 1` of them.
+#[cfg(feature = "std")]
+fn read_offsets<T: io::Read + io::Seek>(
+    s: &mut T,
+    count: u32,
+    flags: StrFlags,
+    layout: Layout,
+    endianness: Endianness,
+) -> Result<Vec<(u64, u64)>, StrMapError> {
+    let table = s.stream_position()?;
+    let values = read_table(s, count, layout, endianness)?;
+
+    check_offsets(values.len(), |idx| values[idx], flags, |idx| table + idx as u64 * layout.stride())?;
+    Ok(order::pair_offsets(values, flags))
+}
+
+/// Reads the `count + 1` offsets of the table starting at the current position in a single
+/// sequential pass, leaving the position just past the table.
+#[cfg(feature = "std")]
+fn read_table<T: io::Read + io::Seek>(
+    s: &mut T,
+    count: u32,
+    layout: Layout,
+    endianness: Endianness,
+) -> Result<Vec<u64>, StrMapError> {
+    use std::io::Read;
+
+    let table = s.stream_position()?;
+    let stride = layout.stride();
+
+    // The count comes straight from the file, so don't trust it with an allocation: the buffer
+    // only grows as far as the data actually goes.
+    let mut data = Vec::new();
+    s.by_ref().take((count as u64 + 1) * stride).read_to_end(&mut data)?;
+
+    let values: Vec<_> = data.chunks_exact(stride as usize)
+        .map(|field| decode_offset(field, layout, endianness))
+        .collect();
+    if count as usize + 1 != values.len() {
+        return Err(StrMapError::CountMismatch {
+            position: table + values.len() as u64 * stride,
+            expected: count,
+            actual: values.len().saturating_sub(1) as u32,
+        });
+    }
+    Ok(values)
+}
+
+/// Checks that the `len` offsets returned by `offset` are consistent with one another, using
+/// `position` to report where an offending offset is stored.
+fn check_offsets<F, P>(len: usize, offset: F, flags: StrFlags, position: P) -> Result<(), StrMapError>
+    where F: Fn(usize) -> u64,
+          P: Fn(usize) -> u64
+{
+    // Ordered and randomized indexes list their strings out of file order, so only the final
+    // offset, which marks the end of the text, is guaranteed to be the largest.
+    let end = offset(len - 1);
+    let sequential = !flags.intersects(StrFlags::STR_RANDOM | StrFlags::STR_ORDERED);
+    for idx in 0..len - 1 {
+        let (current, next) = (offset(idx), offset(idx + 1));
+        if sequential && next < current {
+            return Err(StrMapError::OffsetOutOfOrder {
+                position: position(idx + 1),
+                expected: current,
+                actual: next,
+            });
+        }
+
+        if current > end {
+            return Err(StrMapError::OffsetOutOfBounds {
+                position: position(idx),
+                expected: end,
+                actual: current,
+            });
+        }
+    }
+
+    Ok(())
+}
+
 impl IntoIterator for StrMap {
-    type Item = (u32, u32);
+    type Item = (u64, u64);
     type IntoIter = ConsumingStrMapIter;
 
     fn into_iter(self) -> Self::IntoIter {
@@ -178,11 +720,11 @@ impl IntoIterator for StrMap {
 }
 
 pub struct ConsumingStrMapIter {
-    source: vec::IntoIter<(u32, u32)>,
+    source: vec::IntoIter<(u64, u64)>,
 }
 
 impl Iterator for ConsumingStrMapIter {
-    type Item = (u32, u32);
+    type Item = (u64, u64);
 
     fn next(&mut self) -> Option<Self::Item> {
         self.source.next()
@@ -190,7 +732,7 @@ impl Iterator for ConsumingStrMapIter {
 }
 
 impl<'a> IntoIterator for &'a StrMap {
-    type Item = (u32, u32);
+    type Item = (u64, u64);
     type IntoIter = StrMapIter<'a>;
 
     fn into_iter(self) -> Self::IntoIter {
@@ -201,11 +743,11 @@ impl<'a> IntoIterator for &'a StrMap {
 }
 
 pub struct StrMapIter<'a> {
-    source: slice::Iter<'a, (u32, u32)>
+    source: slice::Iter<'a, (u64, u64)>
 }
 
 impl<'a> Iterator for StrMapIter<'a> {
-    type Item = (u32, u32);
+    type Item = (u64, u64);
 
     fn next(&mut self) -> Option<Self::Item> {
         self.source.next().map(|&(left, right)| (left, right))
@@ -214,33 +756,30 @@ impl<'a> Iterator for StrMapIter<'a> {
 
 struct OffsetsIter<I> {
     source: I,
-    last: Option<u32>,
+    last: Option<u64>,
 }
 
 impl<I> OffsetsIter<I> {
     fn new(source: I) -> OffsetsIter<I> {
         OffsetsIter {
-            source: source,
+            source,
             last: None,
         }
     }
 }
 
 impl<I> Iterator for OffsetsIter<I>
-    where I: Iterator<Item = u32>
+    where I: Iterator<Item = u64>
 {
-    type Item = (u32, u32);
+    type Item = (u64, u64);
 
     fn next(&mut self) -> Option<Self::Item> {
-        let next = match self.source.next() {
-            None => return None,
-            Some(item) => item,
-        };
+        let next = self.source.next()?;
 
         match self.last {
             None => {
                 self.last = Some(next);
-                return self.next();
+                self.next()
             },
 
             Some(last) => {
@@ -253,12 +792,19 @@ impl<I> Iterator for OffsetsIter<I>
 }
 
 #[cfg(test)]
+extern crate memmap2;
+#[cfg(all(test, feature = "serde"))]
+extern crate serde_json;
+
+#[cfg(all(test, feature = "std"))]
 mod tests {
-    use std::io::Cursor;
+    use std::io::{self, Cursor};
+    use std::str;
+    use crate::{Endianness, Layout, StrMapError};
 
-    static SAMPLE: &'static str = include_str!("../sample.txt");
-    static SAMPLE_DAT: &'static [u8] = include_bytes!("../sample.txt.dat");
-    static SAMPLE_DAT_64: &'static [u8] = include_bytes!("../sample.txt-64.dat");
+    static SAMPLE: &str = include_str!("../sample.txt");
+    static SAMPLE_DAT: &[u8] = include_bytes!("../sample.txt.dat");
+    static SAMPLE_DAT_64: &[u8] = include_bytes!("../sample.txt-64.dat");
 
     #[test]
     fn can_parse_x86_dat() {
@@ -270,13 +816,297 @@ mod tests {
         parse(SAMPLE_DAT_64);
     }
 
+    #[test]
+    fn can_parse_x64_wide_dat() {
+        let mut dat = SAMPLE_DAT_64.to_vec();
+        for (idx, slot) in dat.chunks_mut(8).enumerate() {
+            if idx != 5 {
+                let (left, right) = slot.split_at_mut(4);
+                left.swap_with_slice(right);
+            }
+        }
+
+        parse(&dat);
+        assert_eq!(Layout::X64Wide, read(&dat).unwrap().layout());
+    }
+
+    #[test]
+    fn reports_layout() {
+        assert_eq!(Layout::X86, read(SAMPLE_DAT).unwrap().layout());
+        assert_eq!(Layout::X64, read(SAMPLE_DAT_64).unwrap().layout());
+    }
+
+    #[test]
+    fn x64_output_matches_strfile() {
+        let mut map = read(SAMPLE_DAT).unwrap();
+        map.version = 1;
+
+        let mut output = Vec::new();
+        map.write_layout(&mut output, Layout::X64).unwrap();
+        assert_eq!(SAMPLE_DAT_64, &*output);
+    }
+
+    #[test]
+    fn every_layout_round_trips() {
+        let map = read(SAMPLE_DAT).unwrap();
+
+        for &layout in &[Layout::X86, Layout::X64, Layout::X64Wide] {
+            let mut output = Vec::new();
+            map.write_layout(&mut output, layout).unwrap();
+            assert_eq!(layout.header_len() + 5 * layout.stride(), output.len() as u64);
+
+            let copy = read(&output).unwrap();
+            assert_eq!(layout, copy.layout());
+            assert_eq!(Endianness::Big, copy.endianness());
+            assert_eq!(map.iter().collect::<Vec<_>>(), copy.iter().collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn little_endian_round_trips() {
+        let map = read(SAMPLE_DAT).unwrap();
+
+        for &layout in &[Layout::X86, Layout::X64, Layout::X64Wide] {
+            let mut output = Vec::new();
+            map.write_layout_endian(&mut output, layout, Endianness::Little).unwrap();
+            assert_eq!(&[2, 0, 0, 0], &output[..4]);
+
+            // A little-endian 64-bit integer is a value followed by padding, just like `X64`.
+            let copy = read(&output).unwrap();
+            assert_eq!(if layout == Layout::X86 { Layout::X86 } else { Layout::X64 }, copy.layout());
+            assert_eq!(Endianness::Little, copy.endianness());
+            assert_eq!(map.iter().collect::<Vec<_>>(), copy.iter().collect::<Vec<_>>());
+
+            let copy = super::StrMap::read_layout(&mut Cursor::new(&output), layout).unwrap();
+            assert_eq!(Endianness::Little, copy.endianness());
+            assert_eq!(map.iter().collect::<Vec<_>>(), copy.iter().collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn offsets_past_4gib_need_a_64bit_layout() {
+        let header = super::Header::new(2, 1, 21, 21, 0, b'%').unwrap();
+        let map = super::StrMap::from_parts(header, Layout::X64Wide, vec![0, 5_000_000_000]).unwrap();
+
+        for &(layout, endianness) in &[(Layout::X64Wide, Endianness::Big), (Layout::X64, Endianness::Little)] {
+            let mut output = Vec::new();
+            map.write_layout_endian(&mut output, layout, endianness).unwrap();
+
+            let copy = read(&output).unwrap();
+            assert_eq!(Some((0, 5_000_000_000)), copy.get(0));
+            assert_eq!(vec![0, 5_000_000_000], copy.offsets());
+        }
+
+        for &(layout, endianness) in &[(Layout::X86, Endianness::Big), (Layout::X64, Endianness::Big)] {
+            let mut output = Vec::new();
+            let error = map.write_layout_endian(&mut output, layout, endianness).unwrap_err();
+            assert_eq!(io::ErrorKind::InvalidInput, error.kind());
+            assert!(output.is_empty());
+        }
+    }
+
+    #[test]
+    fn byte_order_can_be_forced() {
+        match super::StrMap::read_layout_endian(&mut Cursor::new(SAMPLE_DAT), Layout::X86, Endianness::Little) {
+            Err(StrMapError::UnsupportedVersion { position: 0, expected: 2, actual: 0x02000000 }) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn from_parts_matches_read() {
+        let map = read(SAMPLE_DAT).unwrap();
+        let copy = super::StrMap::from_parts(map.header(), Layout::X86, map.offsets()).unwrap();
+
+        assert_eq!(vec![0, 19, 39, 61, 82], copy.offsets());
+        assert_eq!(map.iter().collect::<Vec<_>>(), copy.iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn from_parts_checks_offsets() {
+        let header = read(SAMPLE_DAT).unwrap().header();
+
+        match super::StrMap::from_parts(header, Layout::X86, vec![0, 19, 39, 61]) {
+            Err(StrMapError::CountMismatch { position: 40, expected: 4, actual: 3 }) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        match super::StrMap::from_parts(header, Layout::X64, vec![0, 19, 1, 61, 82]) {
+            Err(StrMapError::OffsetOutOfOrder { position: 64, expected: 19, actual: 1 }) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn header_new_checks_fields() {
+        let header = super::Header::new(2, 4, 21, 17, 0b110, b'%').unwrap();
+        assert!(header.is_ordered() && header.is_rotated() && !header.is_random());
+
+        match super::Header::new(3, 4, 21, 17, 0, b'%') {
+            Err(StrMapError::UnsupportedVersion { position: 0, expected: 2, actual: 3 }) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        match super::Header::new(2, 4, 21, 17, 0x10, b'%') {
+            Err(StrMapError::UnknownFlags { position: 16, expected: 0b1111, actual: 0x10 }) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn version_one_x86_dat_is_not_mistaken_for_x64() {
+        let mut dat = SAMPLE_DAT.to_vec();
+        dat[3] = 1;
+
+        parse(&dat);
+        assert_eq!(1, read(&dat).unwrap().version());
+    }
+
+    #[test]
+    fn truncated_header_is_an_error() {
+        match read(&SAMPLE_DAT[..10]) {
+            Err(StrMapError::TruncatedHeader { position: 8, expected: 24, actual: 10 }) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn unknown_flags_are_an_error() {
+        let mut dat = SAMPLE_DAT.to_vec();
+        dat[19] = 0x80;
+
+        match read(&dat) {
+            Err(StrMapError::UnknownFlags { position: 16, expected: 0b1111, actual: 0x80 }) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn unsupported_version_is_an_error() {
+        let mut dat = SAMPLE_DAT.to_vec();
+        dat[3] = 9;
+
+        match read(&dat) {
+            Err(StrMapError::UnsupportedVersion { position: 0, expected: 2, actual: 9 }) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn missing_offsets_are_a_count_mismatch() {
+        match read(&SAMPLE_DAT[..SAMPLE_DAT.len() - 4]) {
+            Err(StrMapError::CountMismatch { position: 40, expected: 4, actual: 3 }) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn decreasing_offsets_are_an_error() {
+        let mut dat = SAMPLE_DAT.to_vec();
+        dat[35] = 0x01;
+
+        match read(&dat) {
+            Err(StrMapError::OffsetOutOfOrder { position: 32, expected: 0x13, actual: 0x01 }) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn offsets_past_the_end_are_an_error() {
+        let mut dat = SAMPLE_DAT.to_vec();
+        dat[19] = 0b10;
+        dat[31] = 0xff;
+
+        match read(&dat) {
+            Err(StrMapError::OffsetOutOfBounds { position: 28, expected: 0x52, actual: 0xff }) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn random_is_deterministic_with_seeded_rng() {
+        use rand::rngs::StdRng;
+        use rand::SeedableRng;
+
+        let map = read(SAMPLE_DAT).unwrap();
+        let picks: Vec<_> = (0..8).map(|_| map.random_index(&mut StdRng::seed_from_u64(7))).collect();
+        assert!(picks.iter().all(|&idx| idx == picks[0]));
+
+        let mut rng = StdRng::seed_from_u64(7);
+        for _ in 0..100 {
+            let (start, end) = map.random(&mut rng).unwrap();
+            assert!(map.iter().any(|pair| pair == (start, end)));
+        }
+    }
+
+    #[test]
+    fn random_covers_every_string() {
+        use rand::rngs::StdRng;
+        use rand::SeedableRng;
+
+        let map = read(SAMPLE_DAT).unwrap();
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut seen = [false; 4];
+        for _ in 0..200 {
+            seen[map.random_index(&mut rng).unwrap()] = true;
+        }
+        assert_eq!([true; 4], seen);
+    }
+
+    #[test]
+    fn random_on_empty_index_is_none() {
+        let map = crate::StrMapBuilder::new().build(&mut &b""[..]).unwrap();
+        assert_eq!(None, map.random_index(&mut rand::thread_rng()));
+    }
+
+    #[test]
+    fn bare_trailing_delimiter_is_part_of_the_record() {
+        let text = b"a\n%\nb\n%";
+        let map = crate::StrMapBuilder::new().build_bytes(text).unwrap();
+
+        assert_eq!(vec![(0, 4), (4, 7)], map.iter().collect::<Vec<_>>());
+        assert_eq!(Some(&b"b\n%"[..]), map.record(text, 1));
+        assert_eq!(3, map.longest());
+    }
+
+    #[test]
+    fn offset_table_is_read_in_one_pass() {
+        struct CountSeeks<T> {
+            inner: T,
+            seeks: usize,
+        }
+
+        impl<T: io::Read> io::Read for CountSeeks<T> {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                self.inner.read(buf)
+            }
+        }
+
+        impl<T: io::Seek> io::Seek for CountSeeks<T> {
+            fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+                self.seeks += 1;
+                self.inner.seek(pos)
+            }
+        }
+
+        let seeks = |text: &[u8]| {
+            let mut dat = Vec::new();
+            crate::StrMapBuilder::new().build_bytes(text).unwrap().write(&mut dat).unwrap();
+            let mut s = CountSeeks { inner: Cursor::new(dat), seeks: 0 };
+            super::StrMap::read(&mut s).unwrap();
+            s.seeks
+        };
+
+        assert_eq!(seeks(SAMPLE.as_bytes()), seeks("a\n%\n".repeat(1000).as_bytes()));
+    }
+
+    fn read(input: &[u8]) -> Result<super::StrMap, StrMapError> {
+        super::StrMap::read(&mut Cursor::new(input))
+    }
+
     fn parse(input: &[u8]) {
         let mut x = Cursor::new(input);
         let x = super::StrMap::read(&mut x).unwrap();
-        let strings: Vec<_> = x.iter().map(|(start, len)| unsafe {
-            SAMPLE
-                .slice_unchecked(start as usize, len as usize - 2)
-                .trim_right_matches(|c| '%' == c || c.is_whitespace())
+        let strings: Vec<_> = (0..x.len() as usize).map(|idx| {
+            str::from_utf8(x.record(SAMPLE.as_bytes(), idx).unwrap()).unwrap().trim_end()
         }).collect();
 
         let expected = &[
@@ -0,0 +1,166 @@
+use alloc::vec::Vec;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use core::cmp::Ordering;
+
+use crate::{extract, StrFlags, StrMap};
+
+impl StrMap {
+    /// Sorts the index alphabetically by the text of each string, like `strfile -o`, and marks it
+    /// as ordered. `text` is the cookie file this index describes.
+    ///
+    /// As in strfile, leading punctuation and whitespace are ignored when comparing strings. With
+    /// `ignore_case`, upper and lower case letters compare equal, like `strfile -i`. Strings in a
+    /// rotated file are compared as they appear on disk, without decoding them, as strfile
+    /// compares them.
+    pub fn sort(&mut self, text: &[u8], ignore_case: bool) {
+        let delimiter = self.delimiter;
+        let key = |&(start, end): &(u64, u64)| {
+            let record = extract(text, start, end, delimiter).unwrap_or(&[]);
+            let skip = record.iter().position(u8::is_ascii_alphanumeric).unwrap_or(record.len());
+            &record[skip..]
+        };
+
+        self.offsets.sort_by(|a, b| compare(key(a), key(b), ignore_case));
+        self.flags.remove(StrFlags::STR_RANDOM);
+        self.flags.insert(StrFlags::STR_ORDERED);
+    }
+
+    /// Shuffles the index, like `strfile -r`, and marks it as randomized.
+    pub fn shuffle<R: Rng + ?Sized>(&mut self, rng: &mut R) {
+        self.offsets.shuffle(rng);
+        self.flags.remove(StrFlags::STR_ORDERED);
+        self.flags.insert(StrFlags::STR_RANDOM);
+    }
+}
+
+fn compare(a: &[u8], b: &[u8], ignore_case: bool) -> Ordering {
+    let normalize = |&b: &u8| if ignore_case { b.to_ascii_lowercase() } else { b };
+    a.iter().map(normalize).cmp(b.iter().map(normalize))
+}
+
+/// Pairs each offset read from an index with the offset at which its string ends.
+///
+/// In file order, that's just the next offset in the table. Ordered and randomized indexes list
+/// their strings out of file order, so there each string ends where the next string in the file
+/// begins, or at the final offset, which marks the end of the text.
+pub(crate) fn pair_offsets(values: Vec<u64>, flags: StrFlags) -> Vec<(u64, u64)> {
+    use crate::OffsetsIter;
+
+    if !flags.intersects(StrFlags::STR_RANDOM | StrFlags::STR_ORDERED) {
+        return OffsetsIter::new(values.into_iter()).collect();
+    }
+
+    let end = values[values.len() - 1];
+    let starts = &values[..values.len() - 1];
+    let mut sorted = starts.to_vec();
+    sorted.sort_unstable();
+
+    starts.iter().map(|&start| {
+        let next = sorted.partition_point(|&offset| offset <= start);
+        (start, sorted.get(next).cloned().unwrap_or(end))
+    }).collect()
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use std::io::Cursor;
+    use crate::{StrMap, StrMapBuilder};
+
+    static SAMPLE: &[u8] = include_bytes!("../sample.txt");
+
+    fn records(map: &StrMap, text: &[u8]) -> Vec<String> {
+        (0..map.len() as usize).map(|idx| String::from_utf8(map.record(text, idx).unwrap().to_vec()).unwrap()).collect()
+    }
+
+    fn build(text: &[u8]) -> StrMap {
+        StrMapBuilder::new().build(&mut &text[..]).unwrap()
+    }
+
+    #[test]
+    fn sorts_alphabetically() {
+        let mut map = build(SAMPLE);
+        map.sort(SAMPLE, false);
+
+        assert!(map.is_ordered());
+        assert_eq!(vec![
+            "A known sample file\n",
+            "Solely to provide\n",
+            "This file exists\n",
+            "To use with strfile\n\n",
+        ], records(&map, SAMPLE));
+    }
+
+    #[test]
+    fn ignores_leading_punctuation() {
+        let text = b"\"zebra\"\n%\n  apple\n%\n";
+        let mut map = build(text);
+        map.sort(text, false);
+
+        assert_eq!(vec!["  apple\n", "\"zebra\"\n"], records(&map, text));
+    }
+
+    #[test]
+    fn case_folding() {
+        let text = b"b\n%\nA\n%\na\n%\nB\n";
+
+        let mut map = build(text);
+        map.sort(text, false);
+        assert_eq!(vec!["A\n", "B\n", "a\n", "b\n"], records(&map, text));
+
+        let mut map = build(text);
+        map.sort(text, true);
+        assert_eq!(vec!["A\n", "a\n", "b\n", "B\n"], records(&map, text));
+    }
+
+    #[test]
+    fn rotated_strings_sort_as_stored() {
+        let text = b"a one\n%\nn two\n";
+        let map = StrMapBuilder::new().ordered(true).rotated(true).build(&mut &text[..]).unwrap();
+
+        assert_eq!(vec![0, 8, 14], map.offsets());
+    }
+
+    #[test]
+    fn builder_orders() {
+        let map = StrMapBuilder::new().ordered(true).build(&mut &SAMPLE[..]).unwrap();
+
+        assert!(map.is_ordered());
+        assert_eq!("A known sample file\n", records(&map, SAMPLE)[0]);
+    }
+
+    #[test]
+    fn shuffles_with_seeded_rng() {
+        let mut map = build(SAMPLE);
+        map.shuffle(&mut StdRng::seed_from_u64(5));
+
+        let mut again = build(SAMPLE);
+        again.shuffle(&mut StdRng::seed_from_u64(5));
+
+        assert!(map.is_random());
+        assert_eq!(map.iter().collect::<Vec<_>>(), again.iter().collect::<Vec<_>>());
+
+        let mut shuffled = records(&map, SAMPLE);
+        shuffled.sort();
+        let mut original = records(&build(SAMPLE), SAMPLE);
+        original.sort();
+        assert_eq!(original, shuffled);
+    }
+
+    #[test]
+    fn ordered_index_round_trips() {
+        let mut map = build(SAMPLE);
+        map.sort(SAMPLE, false);
+
+        let mut dat = Vec::new();
+        map.write(&mut dat).unwrap();
+        assert_eq!(&[0, 0, 0, 0x27], &dat[24..28]);
+        assert_eq!(&[0, 0, 0, 0x52], &dat[dat.len() - 4..]);
+
+        let read = StrMap::read(&mut Cursor::new(dat)).unwrap();
+        assert!(read.is_ordered());
+        assert_eq!(map.iter().collect::<Vec<_>>(), read.iter().collect::<Vec<_>>());
+    }
+}
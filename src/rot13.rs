@@ -0,0 +1,58 @@
+use alloc::vec::Vec;
+
+/// Applies rot13 to `input`, leaving anything other than ASCII letters untouched.
+///
+/// rot13 is its own inverse, so this both encodes and decodes.
+pub fn rot13(input: &[u8]) -> Vec<u8> {
+    let mut output = input.to_vec();
+    rot13_in_place(&mut output);
+    output
+}
+
+/// Applies rot13 to `buf` in place.
+pub fn rot13_in_place(buf: &mut [u8]) {
+    for b in buf {
+        *b = match *b {
+            b'a'..=b'm' | b'A'..=b'M' => *b + 13,
+            b'n'..=b'z' | b'N'..=b'Z' => *b - 13,
+            _ => *b,
+        };
+    }
+}
+
+/// Applies rot13 to the strings in a whole cookie file, skipping delimiter lines so that the
+/// offsets of an existing index remain valid even when the delimiter is a letter.
+#[cfg(feature = "std")]
+pub(crate) fn rot13_text(text: &[u8], delimiter: u8) -> Vec<u8> {
+    let mut output = text.to_vec();
+    for line in output.split_mut(|&b| b == b'\n') {
+        if line != [delimiter] {
+            rot13_in_place(line);
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::rot13;
+
+    #[test]
+    fn rotates_letters_only() {
+        assert_eq!(b"Uryyb, jbeyq! 42\n".to_vec(), rot13(b"Hello, world! 42\n"));
+    }
+
+    #[test]
+    fn is_its_own_inverse() {
+        let text = b"The quick brown fox jumps over the lazy dog.";
+        assert_eq!(text.to_vec(), rot13(&rot13(text)));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn text_keeps_delimiter_lines() {
+        use super::rot13_text;
+
+        assert_eq!(b"n\nx\no\nx\n".to_vec(), rot13_text(b"a\nx\nb\nx\n", b'x'));
+    }
+}
@@ -0,0 +1,131 @@
+use memchr::memmem::Finder;
+
+/// Texts at least this long are searched in parallel.
+#[cfg(feature = "rayon")]
+const PARALLEL_THRESHOLD: usize = 64 << 20;
+
+/// The share of the text each parallel task searches.
+#[cfg(feature = "rayon")]
+const CHUNK_LEN: usize = 8 << 20;
+
+/// Finds every line of `text` consisting of `delimiter` alone, returning the position just past
+/// each one, in order.
+pub(crate) fn delimiter_lines(text: &[u8], delimiter: u8) -> Vec<u64> {
+    #[cfg(feature = "rayon")]
+    {
+        if text.len() >= PARALLEL_THRESHOLD {
+            return delimiter_lines_parallel(text, delimiter, CHUNK_LEN);
+        }
+    }
+    delimiter_lines_in(text, delimiter, 0, text.len())
+}
+
+/// Splits `text` into chunks of `chunk_len` bytes and searches them in parallel.
+///
+/// Each chunk claims the delimiter lines whose preceding newline lies within it, so a line
+/// straddling a boundary is found exactly once, and joining the results in chunk order gives the
+/// same positions as a single pass.
+#[cfg(feature = "rayon")]
+fn delimiter_lines_parallel(text: &[u8], delimiter: u8, chunk_len: usize) -> Vec<u64> {
+    use rayon::prelude::*;
+
+    let chunks: Vec<Vec<u64>> = (0..text.len().div_ceil(chunk_len))
+        .into_par_iter()
+        .map(|idx| {
+            let start = idx * chunk_len;
+            delimiter_lines_in(text, delimiter, start, (start + chunk_len).min(text.len()))
+        })
+        .collect();
+    chunks.concat()
+}
+
+/// Finds the delimiter lines whose preceding newline lies in `start..end`, along with a delimiter
+/// line opening the text if `start` is zero.
+fn delimiter_lines_in(text: &[u8], delimiter: u8, start: usize, end: usize) -> Vec<u64> {
+    let mut positions = Vec::new();
+
+    // Every line ends at its first newline, so a newline delimiter can never make up a line alone.
+    if delimiter == b'\n' {
+        return positions;
+    }
+
+    if start == 0 && text.starts_with(&[delimiter, b'\n']) {
+        positions.push(2);
+    }
+
+    // A match beginning before `end` may run up to two bytes past it.
+    let window = &text[..text.len().min(end + 2)];
+    let needle = [b'\n', delimiter, b'\n'];
+    let finder = Finder::new(&needle);
+
+    let mut from = start;
+    while let Some(idx) = finder.find(&window[from..]) {
+        let idx = from + idx;
+        positions.push(idx as u64 + 3);
+
+        // The newline closing this delimiter line may open the next one.
+        from = idx + 2;
+    }
+    positions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::delimiter_lines;
+
+    static SAMPLE: &[u8] = include_bytes!("../sample.txt");
+
+    static TEXTS: &[&[u8]] = &[
+        b"",
+        b"%",
+        b"%\n",
+        b"\n%\n",
+        b"%\n%\n%\n",
+        b"a\n%\nb\n%\n",
+        b"a\n%%\n%\r\n%\n\n%\nb",
+        b"a\n%\n%\nbc\n%\n",
+    ];
+
+    /// Finds delimiter lines one line at a time, the way `StrMapBuilder::build` does.
+    fn line_by_line(text: &[u8], delimiter: u8) -> Vec<u64> {
+        let mut positions = Vec::new();
+        let mut pos = 0;
+        for line in text.split_inclusive(|&b| b == b'\n') {
+            pos += line.len() as u64;
+            if line == [delimiter, b'\n'] {
+                positions.push(pos);
+            }
+        }
+        positions
+    }
+
+    #[test]
+    fn finds_the_same_lines_as_a_line_by_line_scan() {
+        for &text in TEXTS.iter().chain(Some(&SAMPLE)) {
+            assert_eq!(line_by_line(text, b'%'), delimiter_lines(text, b'%'));
+        }
+    }
+
+    #[test]
+    fn finds_adjacent_delimiter_lines() {
+        assert_eq!(vec![2, 4, 6], delimiter_lines(b"%\n%\n%\n", b'%'));
+    }
+
+    #[test]
+    fn newline_delimiter_finds_nothing() {
+        assert!(delimiter_lines(b"a\n\n\n\nb\n", b'\n').is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "rayon")]
+    fn chunks_join_to_a_single_pass() {
+        use super::delimiter_lines_parallel;
+
+        for &text in TEXTS.iter().chain(Some(&SAMPLE)) {
+            let expected = delimiter_lines(text, b'%');
+            for chunk_len in 1..=text.len().max(1) + 1 {
+                assert_eq!(expected, delimiter_lines_parallel(text, b'%', chunk_len), "chunk_len {}", chunk_len);
+            }
+        }
+    }
+}
@@ -0,0 +1,159 @@
+use regex::bytes::{Regex, RegexBuilder};
+use regex::Error;
+use std::io;
+use std::ptr;
+
+use crate::{Collection, CookieFile};
+
+/// A string that matched a search, identified by the file it came from and its place in that
+/// file's index.
+#[derive(Debug, Clone, Copy)]
+pub struct Match<'a> {
+    file: &'a CookieFile,
+    index: usize,
+    range: (u64, u64),
+}
+
+impl<'a> Match<'a> {
+    /// The file the string came from.
+    pub fn file(&self) -> &'a CookieFile {
+        self.file
+    }
+
+    /// The position of the string in its file's index.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// The start and end of the string in its file, in bytes.
+    pub fn range(&self) -> (u64, u64) {
+        self.range
+    }
+}
+
+/// A regular expression search over cookie files, like `fortune -m`.
+///
+/// Strings in rotated files are decoded before matching.
+#[derive(Debug, Clone)]
+pub struct Search {
+    regex: Regex,
+}
+
+impl Search {
+    /// Compiles `pattern`, optionally ignoring case like `fortune -i -m`.
+    pub fn new(pattern: &str, ignore_case: bool) -> Result<Search, Error> {
+        let regex = RegexBuilder::new(pattern).case_insensitive(ignore_case).build()?;
+        Ok(Search { regex })
+    }
+
+    /// Finds the matching strings in a single file, in index order.
+    pub fn file<'a>(&self, file: &'a CookieFile) -> Vec<Match<'a>> {
+        file.map().iter().enumerate().filter_map(|(index, range)| {
+            let record = file.get(index)?;
+            if self.regex.is_match(&record) {
+                Some(Match { file, index, range })
+            } else {
+                None
+            }
+        }).collect()
+    }
+
+    /// Finds the matching strings in every file of a collection.
+    pub fn collection<'a>(&self, collection: &'a Collection) -> Vec<Match<'a>> {
+        collection.files().flat_map(|file| self.file(file)).collect()
+    }
+}
+
+/// Writes matches the way `fortune -m` does: for each file, its name in parentheses and a
+/// delimiter line, followed by each matching string and another delimiter line.
+pub fn write_matches<W: io::Write>(out: &mut W, matches: &[Match]) -> io::Result<()> {
+    let mut current: Option<&CookieFile> = None;
+
+    for m in matches {
+        let delimiter = m.file.map().delimiter();
+        if current.is_none_or(|file| !ptr::eq(file, m.file)) {
+            let name = m.file.path().file_name().unwrap_or(m.file.path().as_os_str());
+            writeln!(out, "({})", name.to_string_lossy())?;
+            out.write_all(&[delimiter, b'\n'])?;
+            current = Some(m.file);
+        }
+
+        if let Some(record) = m.file.get(m.index) {
+            out.write_all(&record)?;
+        }
+        out.write_all(&[delimiter, b'\n'])?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use crate::{Collection, CookieFile, StrMap, StrMapBuilder};
+    use super::{write_matches, Search};
+
+    static SAMPLE: &str = include_str!("../sample.txt");
+    static SAMPLE_DAT: &[u8] = include_bytes!("../sample.txt.dat");
+
+    fn sample() -> CookieFile {
+        let map = StrMap::read(&mut Cursor::new(SAMPLE_DAT)).unwrap();
+        CookieFile::new("fortunes/sample", map, SAMPLE.as_bytes().to_vec())
+    }
+
+    #[test]
+    fn finds_matching_strings() {
+        let file = sample();
+        let matches = Search::new("exists|sample", false).unwrap().file(&file);
+
+        assert_eq!(vec![0, 2], matches.iter().map(|m| m.index()).collect::<Vec<_>>());
+        assert_eq!((39, 61), matches[1].range());
+    }
+
+    #[test]
+    fn ignores_case() {
+        let file = sample();
+
+        assert!(Search::new("SOLELY", false).unwrap().file(&file).is_empty());
+        assert_eq!(1, Search::new("SOLELY", true).unwrap().file(&file).len());
+    }
+
+    #[test]
+    fn matches_decoded_text_of_rotated_files() {
+        let file = sample().rotate();
+        assert_eq!(1, Search::new("Solely", false).unwrap().file(&file).len());
+    }
+
+    #[test]
+    fn delimiter_line_is_not_searched() {
+        let file = sample();
+        assert!(Search::new("%", false).unwrap().file(&file).is_empty());
+    }
+
+    #[test]
+    fn searches_collections() {
+        let text = b"another file\n%\nnothing\n".to_vec();
+        let map = StrMapBuilder::new().build(&mut &text[..]).unwrap();
+
+        let mut collection = Collection::new();
+        collection.add(sample());
+        collection.add(CookieFile::new("other", map, text));
+
+        let matches = Search::new("file", false).unwrap().collection(&collection);
+        assert_eq!(4, matches.len());
+        assert_eq!("other", matches[3].file().path().to_str().unwrap());
+    }
+
+    #[test]
+    fn writes_fortune_style_output() {
+        let file = sample();
+        let matches = Search::new("exists|sample", false).unwrap().file(&file);
+        let mut out = Vec::new();
+        write_matches(&mut out, &matches).unwrap();
+
+        assert_eq!(
+            "(sample)\n%\nThis file exists\n%\nA known sample file\n%\n",
+            String::from_utf8(out).unwrap()
+        );
+    }
+}
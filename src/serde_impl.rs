@@ -0,0 +1,205 @@
+//! Serialization for indexes, enabled by the `serde` feature.
+//!
+//! A `Header` serializes as its six fields, with the flags spelled out by name. A `StrMap` adds
+//! its layout, its byte order if that isn't big-endian, and its offset table, in the same order as
+//! on disk; serialize `StrMap::header` instead to leave the offsets out. Flags may be
+//! deserialized from either names or raw bits, and everything deserialized is checked the way
+//! `StrMap::read` checks an index.
+
+use alloc::borrow::ToOwned;
+use alloc::string::String;
+use alloc::vec::Vec;
+use serde::de::{self, Deserializer};
+use serde::ser::Serializer;
+use serde::{Deserialize, Serialize};
+
+use crate::{Endianness, Header, Layout, StrFlags, StrMap, FLAG_NAMES};
+
+#[derive(Serialize, Deserialize)]
+struct HeaderRepr {
+    version: u32,
+    count: u32,
+    longest: u32,
+    shortest: u32,
+    flags: FlagsRepr,
+    delimiter: char,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum FlagsRepr {
+    Bits(u32),
+    Names(Vec<String>),
+}
+
+#[derive(Serialize, Deserialize)]
+struct StrMapRepr {
+    #[serde(default = "default_layout")]
+    layout: Layout,
+    #[serde(default = "default_endianness", skip_serializing_if = "is_big_endian")]
+    endianness: Endianness,
+    #[serde(flatten)]
+    header: Header,
+    offsets: Vec<u64>,
+}
+
+fn default_layout() -> Layout {
+    Layout::X86
+}
+
+fn default_endianness() -> Endianness {
+    Endianness::Big
+}
+
+fn is_big_endian(endianness: &Endianness) -> bool {
+    *endianness == Endianness::Big
+}
+
+impl Serialize for Header {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let names = FLAG_NAMES.iter()
+            .filter(|&&(flag, _)| self.flags.contains(flag))
+            .map(|&(_, name)| name.to_owned())
+            .collect();
+
+        HeaderRepr {
+            version: self.version,
+            count: self.count,
+            longest: self.longest,
+            shortest: self.shortest,
+            flags: FlagsRepr::Names(names),
+            delimiter: self.delimiter as char,
+        }.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Header {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Header, D::Error> {
+        let repr = HeaderRepr::deserialize(deserializer)?;
+
+        let flags = match repr.flags {
+            FlagsRepr::Bits(bits) => bits,
+            FlagsRepr::Names(names) => {
+                let mut flags = StrFlags::empty();
+                for name in names {
+                    let &(flag, _) = FLAG_NAMES.iter()
+                        .find(|&&(_, known)| known == name)
+                        .ok_or_else(|| de::Error::custom(alloc::format!("unknown flag: {}", name)))?;
+                    flags.insert(flag);
+                }
+                flags.bits()
+            }
+        };
+
+        let delimiter = repr.delimiter as u32;
+        if delimiter > 0xff {
+            return Err(de::Error::custom(alloc::format!("delimiter is not a single byte: {:?}", repr.delimiter)));
+        }
+
+        Header::new(repr.version, repr.count, repr.longest, repr.shortest, flags, delimiter as u8)
+            .map_err(de::Error::custom)
+    }
+}
+
+impl Serialize for StrMap {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        StrMapRepr {
+            layout: self.layout,
+            endianness: self.endianness,
+            header: self.header(),
+            offsets: self.offsets(),
+        }.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for StrMap {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<StrMap, D::Error> {
+        let repr = StrMapRepr::deserialize(deserializer)?;
+        let mut map = StrMap::from_parts(repr.header, repr.layout, repr.offsets).map_err(de::Error::custom)?;
+        map.endianness = repr.endianness;
+        Ok(map)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use std::io::Cursor;
+    use crate::{Endianness, Header, Layout, StrMap, StrMapBuilder};
+
+    static SAMPLE: &[u8] = include_bytes!("../sample.txt");
+    static SAMPLE_DAT_64: &[u8] = include_bytes!("../sample.txt-64.dat");
+
+    #[test]
+    fn strmap_round_trips() {
+        let map = StrMapBuilder::new().ordered(true).build(&mut &SAMPLE[..]).unwrap();
+        let json = serde_json::to_string(&map).unwrap();
+        let copy: StrMap = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(map.header(), copy.header());
+        assert_eq!(map.iter().collect::<Vec<_>>(), copy.iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn serialized_form() {
+        let map = StrMap::read(&mut Cursor::new(SAMPLE_DAT_64)).unwrap();
+
+        assert_eq!(
+            r#"{"layout":"x64","version":1,"count":4,"longest":21,"shortest":17,"flags":[],"delimiter":"%","offsets":[0,19,39,61,82]}"#,
+            serde_json::to_string(&map).unwrap()
+        );
+        assert_eq!(
+            r#"{"version":1,"count":4,"longest":21,"shortest":17,"flags":[],"delimiter":"%"}"#,
+            serde_json::to_string(&map.header()).unwrap()
+        );
+    }
+
+    #[test]
+    fn flags_may_be_names_or_bits() {
+        let named: Header = serde_json::from_str(
+            r#"{"version":2,"count":0,"longest":0,"shortest":0,"flags":["STR_ROTATED","STR_RANDOM"],"delimiter":"%"}"#
+        ).unwrap();
+        let bits: Header = serde_json::from_str(
+            r#"{"version":2,"count":0,"longest":0,"shortest":0,"flags":5,"delimiter":"%"}"#
+        ).unwrap();
+
+        assert_eq!(named, bits);
+        assert!(named.is_random() && named.is_rotated());
+    }
+
+    #[test]
+    fn little_endian_is_recorded() {
+        let map = StrMapBuilder::new().build(&mut &SAMPLE[..]).unwrap();
+        let mut dat = Vec::new();
+        map.write_layout_endian(&mut dat, Layout::X86, Endianness::Little).unwrap();
+        let map = StrMap::read(&mut Cursor::new(dat)).unwrap();
+
+        let json = serde_json::to_string(&map).unwrap();
+        assert!(json.starts_with(r#"{"layout":"x86","endianness":"little","version":2,"#));
+        assert_eq!(Endianness::Little, serde_json::from_str::<StrMap>(&json).unwrap().endianness());
+    }
+
+    #[test]
+    fn layout_defaults_to_x86() {
+        let map: StrMap = serde_json::from_str(
+            r#"{"version":2,"count":1,"longest":2,"shortest":2,"flags":0,"delimiter":"%","offsets":[0,4]}"#
+        ).unwrap();
+        assert_eq!(Layout::X86, map.layout());
+    }
+
+    #[test]
+    fn inconsistent_values_are_rejected() {
+        let invalid = [
+            r#"{"version":9,"count":0,"longest":0,"shortest":0,"flags":0,"delimiter":"%","offsets":[0]}"#,
+            r#"{"version":2,"count":0,"longest":0,"shortest":0,"flags":64,"delimiter":"%","offsets":[0]}"#,
+            r#"{"version":2,"count":0,"longest":0,"shortest":0,"flags":["STR_SHOUTY"],"delimiter":"%","offsets":[0]}"#,
+            r#"{"version":2,"count":0,"longest":0,"shortest":0,"flags":0,"delimiter":"€","offsets":[0]}"#,
+            r#"{"version":2,"count":2,"longest":0,"shortest":0,"flags":0,"delimiter":"%","offsets":[0,4]}"#,
+            r#"{"version":2,"count":2,"longest":0,"shortest":0,"flags":0,"delimiter":"%","offsets":[4,0,8]}"#,
+            r#"{"version":2,"count":1,"longest":0,"shortest":0,"flags":0,"delimiter":"%"}"#,
+        ];
+
+        for json in &invalid {
+            assert!(serde_json::from_str::<StrMap>(json).is_err(), "accepted {}", json);
+        }
+    }
+}
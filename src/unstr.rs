@@ -0,0 +1,89 @@
+use std::io;
+
+use crate::StrMap;
+
+impl StrMap {
+    /// Writes the strings of `text` to `out` in the order this index lists them, like `unstr`.
+    ///
+    /// Each string is followed by a delimiter line, using `delimiter` if given (like `unstr -c`)
+    /// or this index's own delimiter otherwise. Running the result through strfile produces an
+    /// index in plain file order. Strings are copied as they appear in `text`, so rotated files
+    /// stay rotated.
+    pub fn unstr<W: io::Write>(&self, text: &[u8], out: &mut W, delimiter: Option<u8>) -> io::Result<()> {
+        let delimiter = delimiter.unwrap_or(self.delimiter);
+
+        for idx in 0..self.offsets.len() {
+            let record = self.record(text, idx).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("String {} lies outside the text ({} bytes)", idx, text.len()),
+                )
+            })?;
+
+            out.write_all(record)?;
+            if !record.is_empty() && !record.ends_with(b"\n") {
+                out.write_all(b"\n")?;
+            }
+            out.write_all(&[delimiter, b'\n'])?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{StrMap, StrMapBuilder};
+
+    static SAMPLE: &[u8] = include_bytes!("../sample.txt");
+
+    fn unstr(map: &StrMap, text: &[u8], delimiter: Option<u8>) -> String {
+        let mut out = Vec::new();
+        map.unstr(text, &mut out, delimiter).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn writes_in_index_order() {
+        let map = StrMapBuilder::new().ordered(true).build(&mut &SAMPLE[..]).unwrap();
+
+        assert_eq!(
+            "A known sample file\n%\nSolely to provide\n%\nThis file exists\n%\nTo use with strfile\n\n%\n",
+            unstr(&map, SAMPLE, None)
+        );
+    }
+
+    #[test]
+    fn changes_delimiter() {
+        let map = StrMapBuilder::new().build(&mut &SAMPLE[..]).unwrap();
+        let output = unstr(&map, SAMPLE, Some(b'#'));
+
+        assert!(output.starts_with("This file exists\n#\nSolely to provide\n#\n"));
+        assert!(!output.contains('%'));
+    }
+
+    #[test]
+    fn terminates_final_line() {
+        let text = b"b\n%\na";
+        let map = StrMapBuilder::new().ordered(true).build(&mut &text[..]).unwrap();
+
+        assert_eq!("a\n%\nb\n%\n", unstr(&map, text, None));
+    }
+
+    #[test]
+    fn output_reindexes_in_file_order() {
+        let map = StrMapBuilder::new().ordered(true).build(&mut &SAMPLE[..]).unwrap();
+        let output = unstr(&map, SAMPLE, None);
+        let reindexed = StrMapBuilder::new().build(&mut output.as_bytes()).unwrap();
+
+        assert_eq!(4, reindexed.len());
+        assert!(!reindexed.is_ordered());
+        assert_eq!(Some(&b"A known sample file\n"[..]), reindexed.record(output.as_bytes(), 0));
+    }
+
+    #[test]
+    fn text_too_short_is_an_error() {
+        let map = StrMapBuilder::new().build(&mut &SAMPLE[..]).unwrap();
+        assert!(map.unstr(&SAMPLE[..10], &mut Vec::new(), None).is_err());
+    }
+}
@@ -0,0 +1,185 @@
+use std::fmt;
+
+use crate::{StrMap, StrMapBuilder};
+
+/// A single way in which an index disagrees with the text it is supposed to describe.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Discrepancy {
+    /// The string at `index` starts before the string preceding it, in an index that is neither
+    /// ordered nor randomized.
+    OffsetOutOfOrder { index: usize, previous: u64, offset: u64 },
+
+    /// The string at `index` starts beyond the end of the text.
+    OffsetOutOfBounds { index: usize, offset: u64, text_len: usize },
+
+    /// The string at `index` does not start immediately after a delimiter line.
+    Misaligned { index: usize, offset: u64 },
+
+    /// The header's string count does not match the number of strings in the text.
+    CountMismatch { header: u32, text: u32 },
+
+    /// The header's longest string length does not match the text.
+    LongestMismatch { header: u32, text: u32 },
+
+    /// The header's shortest string length does not match the text.
+    ShortestMismatch { header: u32, text: u32 },
+
+    /// The final offset, which should mark the end of the text, does not equal its length.
+    EndMismatch { offset: u64, text_len: usize },
+}
+
+impl fmt::Display for Discrepancy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Discrepancy::OffsetOutOfOrder { index, previous, offset } => {
+                write!(f, "String {} starts at {}, before the preceding string at {}", index, offset, previous)
+            }
+            Discrepancy::OffsetOutOfBounds { index, offset, text_len } => {
+                write!(f, "String {} starts at {}, past the end of the text ({} bytes)", index, offset, text_len)
+            }
+            Discrepancy::Misaligned { index, offset } => {
+                write!(f, "String {} starts at {}, which does not follow a delimiter line", index, offset)
+            }
+            Discrepancy::CountMismatch { header, text } => {
+                write!(f, "Header counts {} strings, text has {}", header, text)
+            }
+            Discrepancy::LongestMismatch { header, text } => {
+                write!(f, "Header says the longest string is {} bytes, text says {}", header, text)
+            }
+            Discrepancy::ShortestMismatch { header, text } => {
+                write!(f, "Header says the shortest string is {} bytes, text says {}", header, text)
+            }
+            Discrepancy::EndMismatch { offset, text_len } => {
+                write!(f, "Final offset is {}, but the text is {} bytes long", offset, text_len)
+            }
+        }
+    }
+}
+
+/// The result of checking an index against its text. See `StrMap::validate`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ValidationReport {
+    discrepancies: Vec<Discrepancy>,
+}
+
+impl ValidationReport {
+    /// Whether the index agrees with the text in every respect.
+    pub fn is_valid(&self) -> bool {
+        self.discrepancies.is_empty()
+    }
+
+    /// Every discrepancy found, in the order they were found.
+    pub fn discrepancies(&self) -> &[Discrepancy] {
+        &self.discrepancies
+    }
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_valid() {
+            return write!(f, "Index is consistent with text");
+        }
+
+        for discrepancy in &self.discrepancies {
+            writeln!(f, "{}", discrepancy)?;
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn validate(map: &StrMap, text: &[u8]) -> ValidationReport {
+    let mut discrepancies = Vec::new();
+    let sequential = !map.is_ordered() && !map.is_random();
+    let delimiter = map.delimiter();
+
+    let mut previous = 0;
+    for (index, (offset, _)) in map.iter().enumerate() {
+        if sequential && offset < previous {
+            discrepancies.push(Discrepancy::OffsetOutOfOrder { index, previous, offset });
+        }
+        previous = offset;
+
+        if offset > text.len() as u64 {
+            discrepancies.push(Discrepancy::OffsetOutOfBounds { index, offset, text_len: text.len() });
+        } else if !follows_delimiter(text, offset as usize, delimiter) {
+            discrepancies.push(Discrepancy::Misaligned { index, offset });
+        }
+    }
+
+    // Scanning the text again is the simplest way to learn what the header should have said.
+    if let Ok(actual) = StrMapBuilder::new().delimiter(delimiter).build(&mut &text[..]) {
+        if actual.len() != map.len() {
+            discrepancies.push(Discrepancy::CountMismatch { header: map.len(), text: actual.len() });
+        }
+        if actual.longest() != map.longest() {
+            discrepancies.push(Discrepancy::LongestMismatch { header: map.longest(), text: actual.longest() });
+        }
+        if actual.shortest() != map.shortest() {
+            discrepancies.push(Discrepancy::ShortestMismatch { header: map.shortest(), text: actual.shortest() });
+        }
+    }
+
+    let end = map.end();
+    if end != text.len() as u64 && !map.is_empty() {
+        discrepancies.push(Discrepancy::EndMismatch { offset: end, text_len: text.len() });
+    }
+
+    ValidationReport { discrepancies }
+}
+
+/// Whether `offset` is the start of the text or falls immediately after a delimiter line.
+fn follows_delimiter(text: &[u8], offset: usize, delimiter: u8) -> bool {
+    if offset == 0 {
+        return true;
+    }
+
+    let before = &text[..offset];
+    before.ends_with(&[delimiter, b'\n']) && (offset == 2 || before[offset - 3] == b'\n')
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use crate::StrMap;
+    use super::Discrepancy;
+
+    static SAMPLE: &[u8] = include_bytes!("../sample.txt");
+    static SAMPLE_DAT: &[u8] = include_bytes!("../sample.txt.dat");
+
+    fn sample() -> StrMap {
+        StrMap::read(&mut Cursor::new(SAMPLE_DAT)).unwrap()
+    }
+
+    #[test]
+    fn sample_is_valid() {
+        assert!(sample().validate(SAMPLE).is_valid());
+    }
+
+    #[test]
+    fn edited_text_is_detected() {
+        let text = b"This file exists\n%\nSolely to provide\n%\nA new line\n%\nA known sample file\n%\nTo use with strfile\n\n";
+        let report = sample().validate(text);
+
+        assert!(!report.is_valid());
+        assert!(report.discrepancies().contains(&Discrepancy::Misaligned { index: 3, offset: 61 }));
+        assert!(report.discrepancies().contains(&Discrepancy::CountMismatch { header: 4, text: 5 }));
+        assert!(report.discrepancies().contains(&Discrepancy::EndMismatch { offset: 82, text_len: text.len() }));
+    }
+
+    #[test]
+    fn truncated_text_is_detected() {
+        let report = sample().validate(&SAMPLE[..50]);
+
+        assert!(report.discrepancies().contains(&Discrepancy::OffsetOutOfBounds { index: 3, offset: 61, text_len: 50 }));
+        assert!(report.discrepancies().contains(&Discrepancy::EndMismatch { offset: 82, text_len: 50 }));
+    }
+
+    #[test]
+    fn header_lengths_are_checked() {
+        let text = b"This file exists\n%\nSolely to provide\n%\nA known sample file\n%\nTo use with strfile, and more\n";
+        let report = sample().validate(text);
+
+        assert!(report.discrepancies().contains(&Discrepancy::LongestMismatch { header: 21, text: 30 }));
+        assert!(report.discrepancies().contains(&Discrepancy::EndMismatch { offset: 82, text_len: 91 }));
+    }
+}